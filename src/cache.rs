@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use image::RgbaImage;
+use log::{debug};
+
+// a picture as it is drawn, or why it couldn't be opened
+pub type Scaled = Result<Arc<RgbaImage>, String>;
+// the path and the size of the slot the picture is fitted into
+pub type Key = (String, (u32, u32));
+
+// scaled pictures that were used lately, so a picture that plays again isn't decoded again.
+// Once the pictures take up more than the budget the ones that weren't used for the longest
+// time are dropped.
+pub struct Cache {
+    budget: usize,
+    used: usize,
+    // counts up with every access, an entry remembers when it was used last
+    clock: u64,
+    entries: HashMap<Key, Entry>,
+}
+
+struct Entry {
+    scaled: Scaled,
+    bytes: usize,
+    used_at: u64,
+}
+
+impl Cache {
+    // the budget is in bytes
+    pub fn new(budget: usize) -> Cache {
+        Cache {
+            budget,
+            used: 0,
+            clock: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &Key) -> Option<Scaled> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.used_at = self.clock;
+        Some(entry.scaled.clone())
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    // adds a picture and drops the least recently used ones until everything fits again,
+    // except for the pinned ones. Those are needed right away, so they may go over the budget.
+    pub fn insert(&mut self, key: Key, scaled: Scaled, pinned: &[Key]) {
+        self.clock += 1;
+        let bytes = match &scaled {
+            Ok(image) => image.len(),
+            Err(error) => error.len(),
+        };
+        if let Some(old) = self.entries.insert(key, Entry { scaled, bytes, used_at: self.clock }) {
+            self.used -= old.bytes;
+        }
+        self.used += bytes;
+
+        while self.used > self.budget {
+            let oldest = self.entries.iter()
+                .filter(|(key, _)| !pinned.contains(key))
+                .min_by_key(|(_, entry)| entry.used_at)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => {
+                    debug!("Dropping {} from the cache", key.0);
+                    if let Some(entry) = self.entries.remove(&key) {
+                        self.used -= entry.bytes;
+                    }
+                },
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 400 bytes each
+    fn picture() -> Scaled {
+        Ok(Arc::new(RgbaImage::new(10, 10)))
+    }
+
+    fn key(path: &str) -> Key {
+        (path.to_string(), (10, 10))
+    }
+
+    #[test]
+    fn the_least_recently_used_picture_goes_first() {
+        let mut cache = Cache::new(1000);
+        cache.insert(key("a"), picture(), &[]);
+        cache.insert(key("b"), picture(), &[]);
+        // a was used after b was added, so b is older now
+        assert!(cache.get(&key("a")).is_some());
+        cache.insert(key("c"), picture(), &[]);
+        assert!(cache.contains(&key("a")));
+        assert!(!cache.contains(&key("b")));
+        assert!(cache.contains(&key("c")));
+    }
+
+    #[test]
+    fn pinned_pictures_stay_over_the_budget() {
+        let mut cache = Cache::new(1000);
+        let pinned = [key("a"), key("b"), key("c")];
+        for key in &pinned {
+            cache.insert(key.clone(), picture(), &pinned);
+        }
+        assert!(pinned.iter().all(|key| cache.contains(key)));
+        // the next one only fits once the oldest pinned ones aren't needed anymore
+        cache.insert(key("d"), picture(), &pinned[2..]);
+        assert!(!cache.contains(&key("a")));
+        assert!(!cache.contains(&key("b")));
+        assert!(cache.contains(&key("c")));
+        assert!(cache.contains(&key("d")));
+    }
+
+    #[test]
+    fn replacing_a_picture_counts_it_once() {
+        let mut cache = Cache::new(1000);
+        for _ in 0..5 {
+            cache.insert(key("a"), picture(), &[]);
+        }
+        cache.insert(key("b"), picture(), &[]);
+        assert!(cache.contains(&key("a")));
+        assert!(cache.contains(&key("b")));
+    }
+}
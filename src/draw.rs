@@ -1,16 +1,22 @@
-use image::{DynamicImage, RgbaImage, GenericImage, Pixel};
+use image::{DynamicImage, RgbaImage, GenericImage, Rgba};
 use minifb::{Window, WindowOptions, Scale};
 
 use minifb::ScaleMode::AspectRatioStretch;
-use std::cmp;
 use log::{debug};
-use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
 
-const MARGIN: u32 = 15;
-pub const BUFFER_HEIGHT: u32 = 800;
-pub const BUFFER_WIDTH: u32 = 1000;
+use image_viewer::resample::{self, Filter};
+use image_viewer::tournament::Tournament;
+
+use super::font;
+use super::prefetch::Prefetcher;
 
-const IMG_WIDTH: u32 = (BUFFER_WIDTH - MARGIN * 3) / 2;
+const MARGIN: u32 = 15;
+// the window opens at this size unless told otherwise, the buffer always follows the window
+pub const WINDOW_HEIGHT: u32 = 800;
+pub const WINDOW_WIDTH: u32 = 1000;
 
 pub struct Buffer {
     pub bytes: Vec<u32>,
@@ -18,38 +24,102 @@ pub struct Buffer {
     pub width: u32,
 }
 
+// what is behind the pictures, the checkerboard shows which parts of a picture are transparent
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Background {
+    #[default]
+    Black,
+    Grey,
+    Checkerboard,
+}
+
+impl Background {
+    pub fn from_arg(arg: &str) -> Result<Background, &'static str> {
+        match arg {
+            "black" => Ok(Background::Black),
+            "grey" | "gray" => Ok(Background::Grey),
+            "checkerboard" => Ok(Background::Checkerboard),
+            _ => Err("Unrecognized background"),
+        }
+    }
+
+    // the colour around the pictures
+    fn fill(self) -> u32 {
+        match self {
+            Background::Grey => GREY,
+            Background::Black | Background::Checkerboard => BLACK,
+        }
+    }
+}
+
+const BLACK: u32 = 0xFF000000;
+const GREY: u32 = 0xFF808080;
+const CHECKER_LIGHT: u32 = 0xFF999999;
+const CHECKER_DARK: u32 = 0xFF666666;
+const CHECKER_SIZE: usize = 16;
+
+// the pixels are premultiplied ARGB, everything that is drawn ends up fully opaque
 impl Buffer {
-    fn new(width: u32, height: u32) -> Buffer {
+    fn new(width: u32, height: u32, background: Background) -> Buffer {
         Buffer {
-            bytes: vec![0; (width * height) as usize],
-            width: width,
-            height: height,
+            bytes: vec![background.fill(); (width * height) as usize],
+            width,
+            height,
         }
     }
 
-    // iterates over the buffer and adds the rgba channels of all passed layers
-    // on a per pixel basis
-    fn write_buffer(mut self, layers: &[Vec<u32>]) -> Self {
-        for y in 0..(self.height) {
-            for x in 0..(self.width) {
-                let index = x + y * self.width;
-                let mut r: u8 = 0;
-                let mut g: u8 = 0;
-                let mut b: u8 = 0;
-                let mut a: u8 = 0;
-
-                for layer in layers {
-                    let rgba = Buffer::buffer_pixel_to_rgba(layer[index as usize]);
-                    r = Buffer::add_single_channel(r, rgba.0);
-                    g = Buffer::add_single_channel(g, rgba.1);
-                    b = Buffer::add_single_channel(b, rgba.2);
-                    a = Buffer::add_single_channel(a, rgba.3);
-                }
+    // a checkerboard in the part of the buffer a picture covers, the squares start at its corner
+    fn checkerboard(&mut self, column: u32, row: u32, width: u32, height: u32) {
+        let buffer_width = self.width as usize;
+        let column = column.min(self.width) as usize;
+        let row = row.min(self.height) as usize;
+        let width = (width as usize).min(buffer_width - column);
+        let height = (height as usize).min(self.height as usize - row);
+        for (y, target_row) in self.bytes.chunks_exact_mut(buffer_width).skip(row).take(height).enumerate() {
+            for (x, pixel) in target_row[column..column + width].iter_mut().enumerate() {
+                *pixel = if (x / CHECKER_SIZE + y / CHECKER_SIZE).is_multiple_of(2) { CHECKER_LIGHT } else { CHECKER_DARK };
+            }
+        }
+    }
+
+    // copies a picture into the buffer with its top left corner at column | row, one row of
+    // pixels at a time. Whatever sticks out of the buffer is cut off. Big pictures are split into
+    // bands of rows that are copied on all cores.
+    pub fn blit(&mut self, image: &RgbaImage, column: u32, row: u32) {
+        let buffer_width = self.width as usize;
+        let column = column.min(self.width) as usize;
+        let row = row.min(self.height) as usize;
+        let width = (image.width() as usize).min(buffer_width - column);
+        let height = (image.height() as usize).min(self.height as usize - row);
+        if width == 0 || height == 0 {
+            return;
+        }
 
-                self.bytes[index as usize] = Buffer::rgba_to_buffer_pixel(r, g, b, a);
+        let source: &[u8] = image;
+        let source_stride = image.width() as usize * 4;
+        let copy_rows = |target: &mut [u32], first_row: usize| {
+            for (y, target_row) in target.chunks_exact_mut(buffer_width).enumerate() {
+                let start = (first_row + y) * source_stride;
+                let source_row = source[start..start + width * 4].chunks_exact(4);
+                for (pixel, rgba) in target_row[column..column + width].iter_mut().zip(source_row) {
+                    *pixel = over(rgba, *pixel);
+                }
             }
+        };
+
+        let target = &mut self.bytes[row * buffer_width..(row + height) * buffer_width];
+        let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+        if threads == 1 || width * height < PARALLEL_PIXELS {
+            copy_rows(target, 0);
+            return;
         }
-        self
+        let band = height.div_ceil(threads);
+        let copy_rows = &copy_rows;
+        thread::scope(|scope| {
+            for (i, target) in target.chunks_mut(band * buffer_width).enumerate() {
+                scope.spawn(move || copy_rows(target, i * band));
+            }
+        });
     }
 
     pub fn buffer_pixel_to_rgba(rgba: u32) -> (u8, u8, u8, u8) {
@@ -70,202 +140,309 @@ impl Buffer {
         argb |= blue as u32;
         argb
     }    
-
-    pub fn add_single_channel(channel1: u8, channel2: u8) -> u8 {
-        if (channel1 as u32 + channel2 as u32) > 255 {
-            return 255;
-        }
-
-        channel1 + channel2
-    }
-}
-
-#[derive(Debug)]
-struct Coord {
-    column: u32,
-    row: u32,
-}
-
-struct Layer {
-    height: u32,
-    width: u32,
-    // values inclusive I guess?
-    corner_top_left: Coord,
-    // corner_top_right: Coord,
-    corner_bottom_left: Coord,
-    corner_bottom_right: Coord,
-    image: RgbaImage,
 }
 
-
-impl Layer {
-    // Expects that there is exactly one image per layer
-    // paints the whole layer
-    pub fn paint_layer(&self) -> Vec<u32> {
-        let mut buffer: Vec<u32> = vec![0; (self.width * self.height) as usize];
-        
-        for row in 0..self.height {
-            for column in 0..self.width {
-                let index = column + row * self.width;
-
-                // check whether we should draw the image
-                if row >= self.corner_top_left.row && row < self.corner_bottom_left.row && column >= self.corner_top_left.column && column < self.corner_bottom_right.column
-                {
-                    // debug!("Trying to draw px (row, column): {} | {}",  row, column);
-                    // Account for the space on top and to the left of the image
-                    let image_pixel_row = row - self.corner_top_left.row;
-                    let image_pixel_column = column - self.corner_top_left.column;
-
-                    // Paint the actual buffer pixel :)
-                    let mut rgba;
-                    if self.image.dimensions().1 > image_pixel_row && self.image.dimensions().0 > image_pixel_column {
-                        // debug!("Iterator: {} | {}, Image Dimensions: {} | {}", image_pixel_row, image_pixel_column, self.image.dimensions().1, self.image.dimensions().0);
-                        let current_pixel = self.image.get_pixel(image_pixel_column, image_pixel_row);
-                        rgba = current_pixel.to_rgba();
-                        buffer[index as usize] = Buffer::rgba_to_buffer_pixel(rgba[0], rgba[1], rgba[2], rgba[3]);
-                    } else {
-                        // debug!("Fallback to black pixel");
-                        buffer[index as usize] = 0;
-                    }
-                }
-            }
+// pictures smaller than this are copied on one thread, starting more would take longer than
+// the copy itself
+const PARALLEL_PIXELS: usize = 256 * 256;
+
+// Porter-Duff over: lays a straight alpha RGBA pixel over a premultiplied buffer pixel. The
+// picture's colours are premultiplied on the way, whatever shows through is scaled by what the
+// picture leaves uncovered. Nearly all pixels are fully opaque or fully transparent and don't
+// need any maths.
+fn over(rgba: &[u8], pixel: u32) -> u32 {
+    let alpha = rgba[3] as u32;
+    match alpha {
+        255 => Buffer::rgba_to_buffer_pixel(rgba[0], rgba[1], rgba[2], 255),
+        0 => pixel,
+        _ => {
+            let (red, green, blue, target_alpha) = Buffer::buffer_pixel_to_rgba(pixel);
+            let uncovered = 255 - alpha;
+            let mix = |source: u8, target: u8| {
+                ((source as u32 * alpha + target as u32 * uncovered + 127) / 255) as u8
+            };
+            Buffer::rgba_to_buffer_pixel(
+                mix(rgba[0], red),
+                mix(rgba[1], green),
+                mix(rgba[2], blue),
+                (alpha + (target_alpha as u32 * uncovered + 127) / 255) as u8
+            )
         }
-        buffer
     }
 }
 
-pub fn get_window() -> Window{
+// fails without a display, e.g. over ssh
+// minifb can't ask for the size of the screen, so full screen is a window without any
+// decoration in the top left corner, width and height have to be those of the screen
+pub fn get_window(width: u32, height: u32, fullscreen: bool) -> Result<Window, String> {
 
     // Draw in Window
     let window_options = WindowOptions {
-        borderless: false,
-        title: true,
-        resize: true,
+        borderless: fullscreen,
+        title: !fullscreen,
+        resize: !fullscreen,
         scale_mode: AspectRatioStretch,
         scale: Scale::X1,
         topmost: true,
         transparency: false,
+        ..WindowOptions::default()
     };
 
-    let window = Window::new(
+    let mut window = Window::new(
         "UWU FUFU",
-        BUFFER_WIDTH as usize,
-        BUFFER_HEIGHT as usize,
+        width as usize,
+        height as usize,
         window_options
-    );
+    ).map_err(|error| error.to_string())?;
 
-    window.unwrap()
+    if fullscreen {
+        window.set_position(0, 0);
+    }
+    Ok(window)
 }
 
-pub fn buffer_from_image(image: DynamicImage) -> Buffer {
-    env_logger::init;
-
-    debug!("Scaling");
+// the size of the inside of the window in pixels
+pub fn window_size(window: &Window) -> (u32, u32) {
+    let (width, height) = window.get_size();
+    (width as u32, height as u32)
+}
 
-    // Buffer 10, Image 5 => Factor 2
-    let image_scaling_factor_by_width = (BUFFER_WIDTH - MARGIN * 2) as f64 / image.dimensions().0 as f64;
-    let image_scaling_factor_by_height = (BUFFER_HEIGHT - MARGIN * 2) as f64 / image.dimensions().1 as f64;
+// pictures at least this many times wider than high are panoramas, two of them are stacked
+const PANORAMA_RATIO: f64 = 2.0;
 
-    let image_scaling_factor = image_scaling_factor_by_height.min(image_scaling_factor_by_width);
-    debug!("Image Original: {} | {}", image.dimensions().0, image.dimensions().1);
-    debug!("Scaling: {} - Scaling by Height: {}", image_scaling_factor, image_scaling_factor_by_height);
+// the part of the buffer a picture is fitted into
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Slot {
+    pub column: u32,
+    pub row: u32,
+    pub width: u32,
+    pub height: u32,
+}
 
-    let mut image_scaled_width;
-    let mut image_scaled_height;
+// where the pictures of a game go in a buffer of the given size, given their dimensions.
+// One picture gets the whole buffer, two go side by side, or one above the other if both are
+// panoramas.
+pub fn layout((buffer_width, buffer_height): (u32, u32), home: (u32, u32), guest: Option<(u32, u32)>) -> Vec<Slot> {
+    // a tiny window still gets a pixel for each picture
+    let width = buffer_width.saturating_sub(MARGIN * 2).max(1);
+    let height = buffer_height.saturating_sub(MARGIN * 2).max(1);
+    let guest = match guest {
+        Some(guest) => guest,
+        None => return vec![Slot {column: MARGIN, row: MARGIN, width, height}],
+    };
 
-    if image_scaling_factor > 1_f64 {
-        debug!("scale up");
-        image_scaled_width = image.dimensions().0  as f64;
-        image_scaled_height = image.dimensions().1  as f64;
+    let panorama = |(width, height): (u32, u32)| width as f64 >= height as f64 * PANORAMA_RATIO;
+    if panorama(home) && panorama(guest) {
+        let half = buffer_height.saturating_sub(MARGIN * 3).max(2) / 2;
+        vec![
+            Slot {column: MARGIN, row: MARGIN, width, height: half},
+            Slot {column: MARGIN, row: MARGIN * 2 + half, width, height: half},
+        ]
     } else {
-        debug!("scale down");
-        image_scaled_width = image_scaling_factor * image.dimensions().0  as f64;
-        image_scaled_height = image_scaling_factor * image.dimensions().1  as f64;
+        let half = buffer_width.saturating_sub(MARGIN * 3).max(2) / 2;
+        vec![
+            Slot {column: MARGIN, row: MARGIN, width: half, height},
+            Slot {column: MARGIN * 2 + half, row: MARGIN, width: half, height},
+        ]
     }
+}
 
-    debug!("Buffer: {} | {}, Image: {} | {}", BUFFER_WIDTH, BUFFER_HEIGHT, image_scaled_width, image_scaled_height);
-
-    let image_resized = image.resize(
-        image_scaled_width as u32,
-        image_scaled_height as u32,
-        image::imageops::Nearest
+// scales a picture to fit into width x height by both sides, but never bigger than it is.
+// this and decoding are the slow parts
+pub fn scale(image: &DynamicImage, width: u32, height: u32, filter: Filter) -> RgbaImage {
+    let (image_width, image_height) = image.dimensions();
+    let factor = (width as f64 / image_width as f64)
+        .min(height as f64 / image_height as f64)
+        .min(1.0);
+    debug!("Scaling {} | {} by {} into {} | {}", image_width, image_height, factor, width, height);
+
+    let rgba_image = resample::resize(
+        image,
+        ((image_width as f64 * factor).round() as u32).clamp(1, width),
+        ((image_height as f64 * factor).round() as u32).clamp(1, height),
+        filter
     );
+    debug!("image resampled");
+    rgba_image
+}
 
-    let rgba_image = image_resized.to_rgba();
-    debug!("image to rgba done");
+// paints every picture centred in its slot over the background
+pub fn buffer_from_images((buffer_width, buffer_height): (u32, u32), pictures: &[(&RgbaImage, Slot)], background: Background) -> Buffer {
+    let mut buffer = Buffer::new(buffer_width, buffer_height, background);
+    for (image, slot) in pictures {
+        let (width, height) = image.dimensions();
+        let column = slot.column + slot.width.saturating_sub(width) / 2;
+        let row = slot.row + slot.height.saturating_sub(height) / 2;
+        if background == Background::Checkerboard {
+            buffer.checkerboard(column, row, width, height);
+        }
+        buffer.blit(image, column, row);
+    }
+    debug!("all pictures painted");
+    buffer
+}
 
-    let layer = Layer {
-        height: BUFFER_HEIGHT,
-        width: BUFFER_WIDTH,
-        corner_top_left: Coord {column: MARGIN, row: MARGIN},
-        corner_bottom_left: Coord {column: MARGIN, row: rgba_image.dimensions().1 + MARGIN},
-        corner_bottom_right: Coord {column: BUFFER_WIDTH - MARGIN, row: rgba_image.dimensions().1 + MARGIN},
-        image: rgba_image,
+// draws the current game into a buffer of the given size, one picture or two. The scaled
+// pictures come from the prefetcher, a picture that can't be opened is replaced by a placeholder.
+// also returns the pictures that failed with their error
+pub fn buffer_from_game(tournament: &Tournament, prefetcher: &Prefetcher, size: (u32, u32), background: Background) -> (Buffer, Vec<(String, String)>) {
+    let (home, guest) = match tournament.next_pair() {
+        Some(pair) => pair,
+        None => return (Buffer::new(size.0, size.1, background), vec![]),
     };
+    let paths: Vec<&str> = std::iter::once(home).chain(guest).collect();
+    let slots = layout(size, prefetcher.dimensions(home), guest.map(|guest| prefetcher.dimensions(guest)));
+
+    let mut broken = vec![];
+    let images: Vec<Arc<RgbaImage>> = paths.iter()
+        .zip(slots.iter())
+        .map(|(path, slot)| match prefetcher.get(path, (slot.width, slot.height)) {
+            Ok(image) => image,
+            Err(error) => {
+                let image = Arc::new(placeholder(path, &error, slot.width, slot.height));
+                broken.push((path.to_string(), error));
+                image
+            }
+        })
+        .collect();
+
+    let pictures: Vec<(&RgbaImage, Slot)> = images.iter()
+        .map(|image| &**image)
+        .zip(slots)
+        .collect();
+    (buffer_from_images(size, &pictures, background), broken)
+}
 
-    debug!("paint layer start");
-    let painted_layer = layer.paint_layer();
-    debug!("paint  layer done");
-    let buffer = Buffer::new(BUFFER_WIDTH, BUFFER_HEIGHT);
-    buffer.write_buffer(&[painted_layer])
+// a dark grey card with the file name and the error written on it, in a 4:3 card that fits
+// into width x height. The text grows with the card.
+fn placeholder(path: &str, error: &str, width: u32, height: u32) -> RgbaImage {
+    let card_width = width.min(height * 4 / 3).max(1);
+    let card_height = (card_width * 3 / 4).max(1);
+    let scale = (card_width / 240).max(1);
+    let line_height = (font::GLYPH_HEIGHT + 3) * scale;
+    let columns = (card_width.saturating_sub(MARGIN * 2) / ((font::GLYPH_WIDTH + 1) * scale)).max(1) as usize;
+    let name = Path::new(path).file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+
+    let mut lines = vec!["Could not open".to_string()];
+    lines.extend(font::wrap(&name, columns));
+    lines.push(String::new());
+    lines.extend(font::wrap(error, columns));
+    lines.push(String::new());
+    lines.extend(font::wrap("It forfeits its remaining games, press any key to go on", columns));
+
+    let mut image = RgbaImage::from_pixel(card_width, card_height, Rgba([48, 48, 48, 255]));
+    for (i, line) in lines.iter().enumerate() {
+        font::draw_text(&mut image, MARGIN, MARGIN + i as u32 * line_height, scale, line, Rgba([230, 230, 230, 255]));
+    }
+    image
 }
 
-pub fn buffer_from_two_images(image_left: DynamicImage, image_right: DynamicImage) -> Buffer {
-
-    // left image rescaling
-    let left_image_scaling_factor_by_width = (IMG_WIDTH) as f64 / image_left.dimensions().0 as f64;
-    let left_image_scaled_width = left_image_scaling_factor_by_width * image_left.dimensions().0  as f64 ;
-    let left_image_scaled_height = left_image_scaling_factor_by_width * image_left.dimensions().1  as f64;
-   
-    let image_left_resized = image_left.resize(
-        left_image_scaled_width as u32, 
-        left_image_scaled_height as u32, 
-        image::imageops::Nearest
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portraits_go_side_by_side_and_fit_by_height() {
+        let slots = layout((1000, 800), (600, 900), Some((900, 600)));
+        assert_eq!(slots, vec![
+            Slot {column: 15, row: 15, width: 477, height: 770},
+            Slot {column: 507, row: 15, width: 477, height: 770},
+        ]);
+        let portrait = DynamicImage::ImageRgba8(RgbaImage::new(600, 1200));
+        assert_eq!(scale(&portrait, 477, 770, Filter::Nearest).dimensions(), (385, 770));
+        // never bigger than it is
+        assert_eq!(scale(&portrait, 2000, 2000, Filter::Nearest).dimensions(), (600, 1200));
+    }
 
-    // right image rescaling
-    let right_image_scaling_factor_by_width = (IMG_WIDTH) as f64 / image_right.dimensions().0 as f64;
-    let right_image_scaled_width = right_image_scaling_factor_by_width * image_right.dimensions().0  as f64;
-    let right_image_scaled_height = right_image_scaling_factor_by_width * image_right.dimensions().1  as f64;
-    
-    let image_right_resized = image_right.resize(
-        right_image_scaled_width as u32, 
-        right_image_scaled_height as u32, 
-        image::imageops::Nearest
-    );
+    #[test]
+    fn two_panoramas_are_stacked() {
+        let slots = layout((1000, 800), (3000, 1000), Some((4000, 1000)));
+        assert_eq!(slots, vec![
+            Slot {column: 15, row: 15, width: 970, height: 377},
+            Slot {column: 15, row: 407, width: 970, height: 377},
+        ]);
+        // a single panorama still goes next to the other picture
+        let slots = layout((1000, 800), (3000, 1000), Some((1000, 1000)));
+        assert_eq!(slots[1].row, 15);
+    }
 
-    // convert to rgba
-    let rgba_image_left = image_left_resized.to_rgba();
-    let rgba_image_right = image_right_resized.to_rgba();
-
-    let layer_image_left = Layer {
-        height: BUFFER_HEIGHT,
-        width: BUFFER_WIDTH,
-        corner_top_left: Coord {column: MARGIN, row: MARGIN},
-        // corner_top_right: Coord {column: IMG_WIDTH + MARGIN, row: MARGIN},
-        corner_bottom_left: Coord {column: MARGIN, row: rgba_image_left.dimensions().1 + MARGIN},
-        corner_bottom_right: Coord {column: IMG_WIDTH + MARGIN, row: rgba_image_left.dimensions().1 + MARGIN},
-        image: rgba_image_left,
-    };
+    #[test]
+    fn a_tiny_window_still_has_room_for_every_picture() {
+        for size in [(0, 0), (1, 1), (20, 20), (40, 10)] {
+            for guest in [None, Some((10, 10)), Some((30, 10))] {
+                let slots = layout(size, (30, 10), guest);
+                assert_eq!(slots.len(), 1 + guest.iter().count());
+                assert!(slots.iter().all(|slot| slot.width >= 1 && slot.height >= 1), "{:?} {:?}", size, slots);
+            }
+        }
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(300, 100));
+        assert_eq!(scale(&image, 1, 1, Filter::Triangle).dimensions(), (1, 1));
+    }
 
-    let layer_image_right = Layer {
-        height: BUFFER_HEIGHT,
-        width: BUFFER_WIDTH,
-        corner_top_left: Coord {column: MARGIN * 2 + IMG_WIDTH, row: MARGIN},
-        // corner_top_right: Coord {column: IMG_WIDTH * 2, row: MARGIN},
-        corner_bottom_left: Coord {column: MARGIN * 2 + IMG_WIDTH, row: rgba_image_right.dimensions().1 + MARGIN},
-        corner_bottom_right: Coord {column: MARGIN * 2 + IMG_WIDTH * 2, row: rgba_image_right.dimensions().1 + MARGIN},
-        image: rgba_image_right,
-    };
+    // every pixel a different opaque colour
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, (x / 256 + y / 256) as u8, 255]))
+    }
+
+    #[test]
+    fn blit_cuts_off_what_sticks_out() {
+        let mut buffer = Buffer::new(10, 8, Background::Grey);
+        buffer.blit(&gradient(6, 6), 7, 5);
+        for y in 0..8 {
+            for x in 0..10 {
+                let expected = if x >= 7 && y >= 5 {
+                    Buffer::rgba_to_buffer_pixel(x as u8 - 7, y as u8 - 5, 0, 255)
+                } else {
+                    GREY
+                };
+                assert_eq!(buffer.bytes[(y * 10 + x) as usize], expected, "{} | {}", x, y);
+            }
+        }
+        // nothing at all inside
+        buffer.blit(&gradient(6, 6), 10, 0);
+        assert!(buffer.bytes[..7].iter().all(|pixel| *pixel == GREY));
+    }
 
-    // println!("Right Img Layer: {:?} | {:?} | {:?} | {:?}", layer_image_right.corner_top_left, layer_image_right.corner_top_right, layer_image_right.corner_bottom_left, layer_image_right.corner_bottom_right);
-    // create and write the buffer(s)
-    debug!("begin layer painting");
-    let left_buffer = layer_image_left.paint_layer();
-    let right_buffer = layer_image_right.paint_layer();
-    debug!("both layers painted");
+    #[test]
+    fn big_pictures_are_copied_in_bands_all_the_same() {
+        let image = gradient(700, 500);
+        let mut buffer = Buffer::new(800, 600, Background::Black);
+        buffer.blit(&image, 50, 40);
+        for (y, row) in buffer.bytes.chunks_exact(800).enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                let expected = match (x.checked_sub(50), y.checked_sub(40)) {
+                    (Some(x), Some(y)) if x < 700 && y < 500 => {
+                        let rgba = image.get_pixel(x as u32, y as u32);
+                        Buffer::rgba_to_buffer_pixel(rgba[0], rgba[1], rgba[2], rgba[3])
+                    },
+                    _ => BLACK,
+                };
+                assert_eq!(*pixel, expected, "{} | {}", x, y);
+            }
+        }
+    }
 
-    let buffer = Buffer::new(BUFFER_WIDTH, BUFFER_HEIGHT);
-    buffer.write_buffer(&[left_buffer, right_buffer])
+    #[test]
+    fn half_transparent_white_over_black_is_grey() {
+        let pixel = over(&[255, 255, 255, 128], BLACK);
+        assert_eq!(Buffer::buffer_pixel_to_rgba(pixel), (128, 128, 128, 255));
+        // over nothing the colour stays premultiplied, so only half of it is there
+        let pixel = over(&[255, 255, 255, 128], 0);
+        assert_eq!(Buffer::buffer_pixel_to_rgba(pixel), (128, 128, 128, 128));
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn half_transparent_over_an_opaque_colour_mixes_them() {
+        let pixel = over(&[0, 0, 255, 128], Buffer::rgba_to_buffer_pixel(255, 0, 0, 255));
+        assert_eq!(Buffer::buffer_pixel_to_rgba(pixel), (127, 0, 128, 255));
+    }
+
+    #[test]
+    fn opaque_replaces_and_transparent_keeps() {
+        let target = Buffer::rgba_to_buffer_pixel(10, 20, 30, 255);
+        assert_eq!(over(&[1, 2, 3, 255], target), Buffer::rgba_to_buffer_pixel(1, 2, 3, 255));
+        assert_eq!(over(&[1, 2, 3, 0], target), target);
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs;
+
+// just enough of EXIF to describe a photo in one line: camera, exposure and when it was taken
+// only JPEG files carry it, everything else simply has no summary
+pub fn summary(path: &str) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let tiff = exif_segment(&bytes)?;
+    let reader = Reader::new(tiff)?;
+
+    let ifd0 = reader.u32_at(4)? as usize;
+    let make = reader.find(ifd0, 0x010f).and_then(|entry| reader.ascii(entry));
+    let model = reader.find(ifd0, 0x0110).and_then(|entry| reader.ascii(entry));
+    let exif_ifd = reader.find(ifd0, 0x8769).and_then(|entry| reader.long(entry)).map(|offset| offset as usize);
+    let date = exif_ifd
+        .and_then(|ifd| reader.find(ifd, 0x9003))
+        .or_else(|| reader.find(ifd0, 0x0132))
+        .and_then(|entry| reader.ascii(entry));
+
+    let mut parts = vec![];
+    match (make, model) {
+        // most models already start with the make, "Canon Canon EOS 5D" looks silly
+        (Some(make), Some(model)) if model.starts_with(&make) => parts.push(model),
+        (Some(make), Some(model)) => parts.push(format!("{} {}", make, model)),
+        (Some(camera), None) | (None, Some(camera)) => parts.push(camera),
+        (None, None) => {}
+    }
+    if let Some(ifd) = exif_ifd {
+        if let Some((numerator, denominator)) = reader.find(ifd, 0x829a).and_then(|entry| reader.rational(entry)) {
+            if numerator > 0 && numerator < denominator {
+                parts.push(format!("1/{}s", (denominator as f64 / numerator as f64).round()));
+            } else if denominator > 0 {
+                parts.push(format!("{}s", numerator as f64 / denominator as f64));
+            }
+        }
+        if let Some((numerator, denominator)) = reader.find(ifd, 0x829d).and_then(|entry| reader.rational(entry)) {
+            if denominator > 0 {
+                parts.push(format!("f/{:.1}", numerator as f64 / denominator as f64));
+            }
+        }
+        if let Some(iso) = reader.find(ifd, 0x8827).and_then(|entry| reader.short(entry)) {
+            parts.push(format!("ISO {}", iso));
+        }
+        if let Some((numerator, denominator)) = reader.find(ifd, 0x920a).and_then(|entry| reader.rational(entry)) {
+            if denominator > 0 {
+                parts.push(format!("{:.0}mm", numerator as f64 / denominator as f64));
+            }
+        }
+    }
+    if let Some(date) = date {
+        parts.push(date);
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(", "))
+    }
+}
+
+// walks the JPEG segments up to the APP1 segment that holds the TIFF structure
+fn exif_segment(bytes: &[u8]) -> Option<&[u8]> {
+    if bytes.get(0..2)? != [0xff, 0xd8] {
+        return None;
+    }
+    let mut position = 2;
+    while position + 4 <= bytes.len() {
+        if bytes[position] != 0xff {
+            return None;
+        }
+        let marker = bytes[position + 1];
+        let length = u16::from_be_bytes([bytes[position + 2], bytes[position + 3]]) as usize;
+        let segment = bytes.get(position + 4..position + 2 + length)?;
+        if marker == 0xe1 && segment.starts_with(b"Exif\0\0") {
+            return Some(&segment[6..]);
+        }
+        // the image data starts, there is no EXIF after this
+        if marker == 0xda {
+            return None;
+        }
+        position += 2 + length;
+    }
+    None
+}
+
+// TIFF comes in both byte orders, "II" for little and "MM" for big endian
+struct Reader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+// a directory entry: its type, the number of values and where they are
+struct Entry {
+    kind: u16,
+    count: u32,
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Option<Reader<'a>> {
+        let little_endian = match bytes.get(0..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(Reader { bytes, little_endian })
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes = [*self.bytes.get(offset)?, *self.bytes.get(offset + 1)?];
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes.get(offset..offset + 4)?);
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn find(&self, ifd: usize, tag: u16) -> Option<Entry> {
+        let count = self.u16_at(ifd)? as usize;
+        for i in 0..count {
+            let entry = ifd + 2 + i * 12;
+            if self.u16_at(entry)? == tag {
+                return Some(Entry {
+                    kind: self.u16_at(entry + 2)?,
+                    count: self.u32_at(entry + 4)?,
+                    offset: entry + 8,
+                });
+            }
+        }
+        None
+    }
+
+    // values of up to four bytes are stored in the entry itself, longer ones somewhere else
+    fn data(&self, entry: &Entry, size: usize) -> Option<usize> {
+        if size <= 4 {
+            Some(entry.offset)
+        } else {
+            Some(self.u32_at(entry.offset)? as usize)
+        }
+    }
+
+    fn ascii(&self, entry: Entry) -> Option<String> {
+        if entry.kind != 2 {
+            return None;
+        }
+        let start = self.data(&entry, entry.count as usize)?;
+        let bytes = self.bytes.get(start..start + entry.count as usize)?;
+        let text = String::from_utf8_lossy(bytes).trim_end_matches('\0').trim().to_string();
+        if text.is_empty() { None } else { Some(text) }
+    }
+
+    fn short(&self, entry: Entry) -> Option<u16> {
+        match entry.kind {
+            3 => self.u16_at(entry.offset),
+            4 => self.u32_at(entry.offset).map(|value| value as u16),
+            _ => None,
+        }
+    }
+
+    fn long(&self, entry: Entry) -> Option<u32> {
+        match entry.kind {
+            3 => self.u16_at(entry.offset).map(u32::from),
+            4 => self.u32_at(entry.offset),
+            _ => None,
+        }
+    }
+
+    fn rational(&self, entry: Entry) -> Option<(u32, u32)> {
+        if entry.kind != 5 {
+            return None;
+        }
+        let start = self.data(&entry, 8)?;
+        Some((self.u32_at(start)?, self.u32_at(start + 4)?))
+    }
+}
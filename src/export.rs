@@ -0,0 +1,276 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use log::{debug};
+
+use super::tournament::Tournament;
+
+// name of the file the full ranking is written to
+pub const RANKING_FILE: &str = "ranking.csv";
+
+#[derive(Debug, Copy, Clone)]
+pub enum Action {
+    Copy,
+    Move,
+    HardLink,
+}
+
+// what to do when the destination folder already contains a file with the same name
+#[derive(Debug, Copy, Clone)]
+pub enum Collision {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ExportOptions {
+    pub action: Action,
+    pub collision: Collision,
+    // how many pictures of a ranked (league) tournament should be exported
+    pub top: usize,
+}
+
+impl Default for ExportOptions {
+    fn default() -> ExportOptions {
+        ExportOptions {
+            action: Action::Copy,
+            collision: Collision::Rename,
+            top: 1,
+        }
+    }
+}
+
+impl Action {
+    pub fn from_arg(arg: &str) -> Result<Action, &'static str> {
+        match arg {
+            "copy" => Ok(Action::Copy),
+            "move" => Ok(Action::Move),
+            "link" => Ok(Action::HardLink),
+            _ => Err("Unrecognized export action"),
+        }
+    }
+}
+
+impl Collision {
+    pub fn from_arg(arg: &str) -> Result<Collision, &'static str> {
+        match arg {
+            "skip" => Ok(Collision::Skip),
+            "overwrite" => Ok(Collision::Overwrite),
+            "rename" => Ok(Collision::Rename),
+            _ => Err("Unrecognized collision policy"),
+        }
+    }
+}
+
+// copies, moves or links every picture into the destination directory
+// returns the number of pictures that actually ended up there
+pub fn export(paths: &[String], to_directory: &str, options: &ExportOptions) -> io::Result<usize> {
+    fs::create_dir_all(to_directory)?;
+    let mut exported = 0;
+    // the pictures themselves may be in the destination directory, e.g. when it is the source
+    // folder, those must never be overwritten
+    let sources: Vec<PathBuf> = paths.iter()
+        .filter_map(|path| fs::canonicalize(path).ok())
+        .collect();
+    // two pictures with the same name from different sub folders must not replace each other
+    let mut written: Vec<PathBuf> = vec![];
+
+    for path in paths {
+        let source = Path::new(path);
+        let file_name = match source.file_name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let mut destination = Path::new(to_directory).join(file_name);
+        let existing = fs::canonicalize(&destination).ok();
+        if existing.is_some() && existing == fs::canonicalize(source).ok() {
+            println!("{} is in {} already", path, to_directory);
+            written.extend(existing);
+            exported += 1;
+            continue;
+        }
+        if existing.as_ref().is_some_and(|existing| sources.contains(existing) || written.contains(existing)) {
+            destination = free_destination(&destination);
+        } else if destination.exists() {
+            match options.collision {
+                Collision::Skip => {
+                    println!("Skipping {}, it already exists in {}", path, to_directory);
+                    continue;
+                },
+                Collision::Overwrite => {
+                    fs::remove_file(&destination)?;
+                },
+                Collision::Rename => {
+                    destination = free_destination(&destination);
+                }
+            }
+        }
+
+        debug!("{:?} {} -> {}", options.action, path, destination.display());
+        match options.action {
+            Action::Copy => {
+                fs::copy(source, &destination)?;
+            },
+            Action::Move => {
+                // renaming does not work across file systems, so fall back to copy and delete
+                if fs::rename(source, &destination).is_err() {
+                    fs::copy(source, &destination)?;
+                    fs::remove_file(source)?;
+                }
+            },
+            Action::HardLink => {
+                fs::hard_link(source, &destination)?;
+            }
+        }
+        if let Ok(destination) = fs::canonicalize(&destination) {
+            written.push(destination);
+        }
+        exported += 1;
+    }
+
+    Ok(exported)
+}
+
+// writes the standings of every player into ranking.csv inside the destination directory
+pub fn write_ranking(tournament: &Tournament, to_directory: &str) -> io::Result<()> {
+    fs::create_dir_all(to_directory)?;
+    let mut file = fs::File::create(Path::new(to_directory).join(RANKING_FILE))?;
+
+    writeln!(file, "rank,path,rating,deviation,points,in")?;
+    for (rank, player) in tournament.standings().iter().enumerate() {
+        writeln!(
+            file,
+            "{},\"{}\",{:.1},{:.1},{},{}",
+            rank + 1,
+            tournament.paths[player.path_index].replace('"', "\"\""),
+            player.rating.value,
+            player.rating.deviation,
+            player.score,
+            player.is_in
+        )?;
+    }
+    Ok(())
+}
+
+// path and rating of every picture in a ranking.csv written by write_ranking
+pub fn read_ratings(ranking: &str) -> io::Result<Vec<(String, f64)>> {
+    let content = fs::read_to_string(ranking)?;
+    let mut ratings = vec![];
+    // the header is skipped
+    for line in content.lines().skip(1) {
+        // rank,"path",rating,... where quotes inside the path are doubled
+        let rest = match line.split_once(",\"") {
+            Some((_, rest)) => rest,
+            None => continue,
+        };
+        let mut path = String::new();
+        let mut characters = rest.chars().peekable();
+        while let Some(character) = characters.next() {
+            if character == '"' {
+                if characters.peek() == Some(&'"') {
+                    characters.next();
+                } else {
+                    break;
+                }
+            }
+            path.push(character);
+        }
+        let rest: String = characters.collect();
+        // "NaN" and "inf" parse as well, but can't be compared with other ratings
+        let rating = rest.trim_start_matches(',').split(',').next()
+            .and_then(|rating| rating.parse::<f64>().ok())
+            .filter(|rating| rating.is_finite());
+        if let Some(rating) = rating {
+            ratings.push((path, rating));
+        }
+    }
+    Ok(ratings)
+}
+
+// appends -1, -2, ... to the file stem until the name is not taken yet
+fn free_destination(destination: &Path) -> PathBuf {
+    let stem = destination.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = destination.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+    let mut counter = 1;
+    loop {
+        let candidate = destination.with_file_name(format!("{}-{}{}", stem, counter, extension));
+        if !candidate.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use crate::tournament::{Decision, TournamentType};
+
+    fn temporary(name: &str) -> PathBuf {
+        let folder = env::temp_dir().join(format!("image_viewer_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn ranking_reads_back_the_ratings() {
+        let folder = temporary("ranking");
+        let paths = vec!["a.jpg".to_string(), "with \"quotes\".jpg".to_string(), "with, comma.png".to_string()];
+        let mut tournament = Tournament::new(String::new(), paths, TournamentType::League);
+        tournament.generate_round();
+        while !tournament.is_finished() {
+            tournament.submit(Decision::Left);
+        }
+
+        write_ranking(&tournament, &folder.display().to_string()).unwrap();
+        let ratings = read_ratings(&folder.join(RANKING_FILE).display().to_string()).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        let standings = tournament.standings();
+        assert_eq!(ratings.len(), standings.len());
+        for ((path, rating), player) in ratings.iter().zip(standings) {
+            assert_eq!(path, &tournament.paths[player.path_index]);
+            assert!((rating - player.rating.value).abs() <= 0.05);
+        }
+    }
+
+    #[test]
+    fn ratings_that_are_not_numbers_are_left_out() {
+        let folder = temporary("ratings");
+        let ranking = folder.join(RANKING_FILE);
+        fs::write(&ranking, "rank,path,rating,deviation,points,in\n1,\"a.jpg\",NaN,0,0,true\n2,\"b.jpg\",1490.5,0,0,true\n").unwrap();
+        let ratings = read_ratings(&ranking.display().to_string()).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(ratings, vec![("b.jpg".to_string(), 1490.5)]);
+    }
+
+    #[test]
+    fn winners_never_replace_each_other_or_themselves() {
+        let folder = temporary("export");
+        for sub_folder in ["one", "two"] {
+            fs::create_dir_all(folder.join(sub_folder)).unwrap();
+            fs::write(folder.join(sub_folder).join("a.jpg"), sub_folder).unwrap();
+        }
+        let paths = vec![
+            folder.join("one/a.jpg").display().to_string(),
+            folder.join("two/a.jpg").display().to_string(),
+        ];
+        let options = ExportOptions { collision: Collision::Overwrite, ..ExportOptions::default() };
+        // the destination holds the first picture already
+        let exported = export(&paths, &folder.join("one").display().to_string(), &options).unwrap();
+
+        let first = fs::read_to_string(folder.join("one/a.jpg"));
+        let second = fs::read_to_string(folder.join("one/a-1.jpg"));
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(exported, 2);
+        assert_eq!(first.unwrap(), "one");
+        assert_eq!(second.unwrap(), "two");
+    }
+}
@@ -0,0 +1,21 @@
+// The ranking engine without any window: pictures go in as paths, decisions come in one at a time
+// and the standings come out. The minifb frontend in main.rs is just one way to drive it.
+//
+// let mut tournament = Tournament::new(directory, paths, TournamentType::Swiss);
+// tournament.generate_round();
+// while let Some((left, right)) = tournament.next_pair() {
+//     // show left and right somehow, then
+//     tournament.submit(Decision::Left);
+// }
+// let standings = tournament.standings();
+
+pub mod export;
+pub mod picture;
+pub mod rating;
+pub mod resample;
+pub mod scan;
+pub mod script;
+pub mod seeding;
+pub mod session;
+pub mod sort;
+pub mod tournament;
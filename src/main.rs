@@ -1,17 +1,108 @@
+#[cfg(feature = "gui")]
 use minifb::{Key, KeyRepeat};
 use log::{debug};
 use std::env;
+use std::path::Path;
 use std::process;
-use crate::tournament::Tournament;
-use native_dialog::{FileDialog, MessageDialog, MessageType};
+#[cfg(feature = "gui")]
+use std::time::{Duration, Instant};
+use image_viewer::{export, picture, rating, resample, scan, script, seeding, session, tournament};
+#[cfg(feature = "gui")]
+use image_viewer::tournament::Decision;
+use image_viewer::tournament::Tournament;
 
+#[cfg(feature = "gui")]
+mod cache;
+#[cfg(feature = "gui")]
 mod draw;
-mod tournament;
+mod exif;
+#[cfg(feature = "gui")]
+mod font;
+#[cfg(feature = "gui")]
+mod prefetch;
+mod terminal;
+
+const USAGE: &str = "Usage: image_viewer <from> <to> <dating|world_cup|league|swiss|double_elimination|adaptive|sort> \
+[--action copy|move|link] [--on-collision skip|overwrite|rename] [--top N] [--rounds N] \
+[--rating elo[:K]|glicko2[:TAU]] [--resume SESSION] [--frontend window|terminal] [--preview auto|kitty|off] [--script FILE|-] \
+[--seed N] [--seeding random|date|rating[:RANKING]|sequential] [--third-place] \
+[--recursive] [--include GLOB]... [--exclude GLOB]... [--validate] [--cache-mb N] [--preview-size N] \
+[--filter nearest|triangle|catmull-rom|lanczos3] [--window WIDTHxHEIGHT [--fullscreen]] \
+[--background black|grey|checkerboard]";
+
+// how long the window has to keep its size before the game is drawn again at that size
+#[cfg(feature = "gui")]
+const RESIZE_DELAY: Duration = Duration::from_millis(150);
+
+// keys that decide the current game
+#[cfg(feature = "gui")]
+const KEY_DECISIONS: [(Key, Decision); 5] = [
+    (Key::A, Decision::Left),
+    (Key::D, Decision::Right),
+    (Key::W, Decision::Draw),
+    (Key::S, Decision::Skip),
+    (Key::X, Decision::RejectBoth),
+];
+
+// where the games are shown
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Frontend {
+    Window,
+    // for machines without a display, e.g. over ssh
+    Terminal,
+}
+
+impl Frontend {
+    fn from_arg(arg: &str) -> Result<Frontend, &'static str> {
+        match arg {
+            "window" => Ok(Frontend::Window),
+            "terminal" => Ok(Frontend::Terminal),
+            _ => Err("Unrecognized frontend"),
+        }
+    }
+}
 
 struct Args {
     match_type: tournament::TournamentType,
     from_directory: String,
     to_directory: String,
+    export: export::ExportOptions,
+    // overrides the default number of swiss rounds
+    rounds: Option<usize>,
+    rating_system: rating::RatingSystem,
+    // session file to continue from
+    resume: Option<String>,
+    frontend: Frontend,
+    // pictures in the terminal frontend
+    preview: terminal::Preview,
+    // decisions to play without any frontend, "-" reads them from stdin
+    script: Option<String>,
+    // a random one if none is given
+    seed: Option<u64>,
+    seeding: seeding::Seeding,
+    // world cup plays for third place too
+    third_place: bool,
+    // which files of the from folder take part
+    scan: scan::ScanOptions,
+    // opens every picture before the first game
+    validate: bool,
+    // megabytes of scaled pictures the window keeps around
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    cache_size: usize,
+    // longest side of the previews kept on disk, 0 always decodes the full pictures
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    preview_size: u32,
+    // how the window scales pictures
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    filter: resample::Filter,
+    // the size the window opens at, full screen needs the size of the screen
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    window_size: (u32, u32),
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    fullscreen: bool,
+    // what the window shows behind the pictures
+    #[cfg(feature = "gui")]
+    background: draw::Background,
 }
 
 impl Args {
@@ -29,28 +120,145 @@ impl Args {
         };
 
         let match_type = match args.next() {
-            Some(arg) => match &arg[..] {
-                "dating" => tournament::TournamentType::Dating,
-                "world_cup" => tournament::TournamentType::WorldCup,
-                "league" => tournament::TournamentType::League,
-                _ => return Err("Unrecognized match type"),
-            },
+            Some(arg) => tournament::TournamentType::from_arg(&arg)?,
             None => return Err("Didn't receive match type"),
         };
 
+        // everything after the positional arguments is an optional flag with a value
+        let mut export = export::ExportOptions::default();
+        let mut rounds = None;
+        let mut rating_system = rating::RatingSystem::default();
+        let mut resume = None;
+        let mut frontend = Frontend::Window;
+        let mut preview = terminal::Preview::Auto;
+        let mut script = None;
+        let mut seed = None;
+        let mut seeding = seeding::Seeding::Random;
+        let mut third_place = false;
+        let mut scan = scan::ScanOptions::default();
+        let mut validate = false;
+        let mut cache_size = 256;
+        let mut preview_size = 1200;
+        let mut filter = resample::Filter::default();
+        let mut window_size = None;
+        #[cfg(feature = "gui")]
+        let mut background = draw::Background::default();
+        let mut fullscreen = false;
+        while let Some(flag) = args.next() {
+            // flags without a value
+            match &flag[..] {
+                "--third-place" => {
+                    third_place = true;
+                    continue;
+                },
+                "--recursive" => {
+                    scan.recursive = true;
+                    continue;
+                },
+                "--validate" => {
+                    validate = true;
+                    continue;
+                },
+                "--fullscreen" => {
+                    fullscreen = true;
+                    continue;
+                },
+                _ => {}
+            }
+            let value = match args.next() {
+                Some(value) => value,
+                None => return Err("Flag is missing its value"),
+            };
+            match &flag[..] {
+                "--action" => export.action = export::Action::from_arg(&value)?,
+                "--on-collision" => export.collision = export::Collision::from_arg(&value)?,
+                "--top" => export.top = match value.parse() {
+                    Ok(top) => top,
+                    Err(_) => return Err("--top expects a number"),
+                },
+                "--rounds" => rounds = match value.parse() {
+                    Ok(rounds) => Some(rounds),
+                    Err(_) => return Err("--rounds expects a number"),
+                },
+                "--rating" => rating_system = rating::RatingSystem::from_arg(&value)?,
+                "--resume" => resume = Some(value),
+                "--frontend" => frontend = Frontend::from_arg(&value)?,
+                "--preview" => preview = terminal::Preview::from_arg(&value)?,
+                "--script" => script = Some(value),
+                "--seed" => seed = match value.parse() {
+                    Ok(seed) => Some(seed),
+                    Err(_) => return Err("--seed expects a number"),
+                },
+                "--seeding" => seeding = seeding::Seeding::from_arg(&value)?,
+                "--cache-mb" => cache_size = match value.parse() {
+                    Ok(cache_size) => cache_size,
+                    Err(_) => return Err("--cache-mb expects a number"),
+                },
+                "--preview-size" => preview_size = match value.parse() {
+                    Ok(preview_size) => preview_size,
+                    Err(_) => return Err("--preview-size expects a number"),
+                },
+                "--filter" => filter = resample::Filter::from_arg(&value)?,
+                "--window" => window_size = Some(parse_size(&value)?),
+                #[cfg(feature = "gui")]
+                "--background" => background = draw::Background::from_arg(&value)?,
+                // both can be given more than once
+                "--include" => scan.include.push(value),
+                "--exclude" => scan.exclude.push(value),
+                _ => return Err("Unrecognized flag"),
+            }
+        }
+
+        // minifb can't tell how big the screen is
+        if fullscreen && window_size.is_none() {
+            return Err("--fullscreen needs the size of the screen, e.g. --window 1920x1080");
+        }
+        #[cfg(feature = "gui")]
+        let window_size = window_size.unwrap_or((draw::WINDOW_WIDTH, draw::WINDOW_HEIGHT));
+        #[cfg(not(feature = "gui"))]
+        let window_size = window_size.unwrap_or((0, 0));
+
         Ok(Args {
             from_directory,
             to_directory,
             match_type,
+            export,
+            rounds,
+            rating_system,
+            resume,
+            frontend,
+            preview,
+            script,
+            seed,
+            seeding,
+            third_place,
+            scan,
+            validate,
+            cache_size,
+            preview_size,
+            filter,
+            window_size,
+            fullscreen,
+            #[cfg(feature = "gui")]
+            background,
         })
     }
 }
 
+// "1920x1080" to (1920, 1080)
+fn parse_size(arg: &str) -> Result<(u32, u32), &'static str> {
+    let mut parts = arg.splitn(2, 'x');
+    match (parts.next().map(str::parse), parts.next().map(str::parse)) {
+        (Some(Ok(width)), Some(Ok(height))) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err("--window expects a size like 1920x1080"),
+    }
+}
+
 fn main() {
     env_logger::init();
 
     // dialog boxes be like brrrrrrrrrrrrr
-    // let result = FileDialog::new()
+    // let result = native_dialog::FileDialog::new()
     //     .set_location("~/Desktop")
     //     .add_filter("PNG Image", &["png"])
     //     .add_filter("JPEG Image", &["jpg", "jpeg"])
@@ -58,51 +266,323 @@ fn main() {
     //     .unwrap();
     // let message = format!("{:#?}", result);
 
-    // Build the GUI in General
-    let mut window = draw::get_window();
-    window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
-
     // Read relevant data from CMD
-    let arguments: Vec<String> = env::args().collect();
-    let directory_path = arguments[1].clone();
-
-    let category = match &arguments[2][..] {
-        "dating" => tournament::TournamentType::Dating,
-        "world_cup" => tournament::TournamentType::WorldCup,
-        "league" => tournament::TournamentType::League,
-        _ => {
-            eprintln!("Invalid TournamentType: {}", arguments[2]);
+    let args = match Args::new(env::args()) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    // a resumed session keeps saving to the file it came from
+    let session_path = match &args.resume {
+        Some(path) => path.clone(),
+        None => Path::new(&args.to_directory).join(session::SESSION_FILE).display().to_string(),
+    };
+
+    // a new tournament would replace the session in <to>, that is only fine once it is over
+    if args.resume.is_none() && Path::new(&session_path).exists() {
+        let finished = session::load(&session_path).is_ok_and(|tournament| tournament.done);
+        if !finished {
+            eprintln!("{} holds an unfinished session, continue it with --resume {} or delete it to start over", session_path, session_path);
+            process::exit(1);
+        }
+    }
+
+    let mut tournament = match &args.resume {
+        Some(path) => resume_tournament(path, &args),
+        None => {
+            let scan = match scan::scan(&args.from_directory, &args.scan) {
+                Ok(scan) => scan,
+                Err(error) => {
+                    eprintln!("Could not read {}: {}", args.from_directory, error);
+                    process::exit(1);
+                }
+            };
+            if !scan.skipped.is_empty() {
+                println!("Skipped {} file(s):", scan.skipped.len());
+                for (path, reason) in &scan.skipped {
+                    println!("  {} ({})", path, reason);
+                }
+            }
+            let mut paths = scan.paths;
+            // broken pictures never enter the tournament
+            if args.validate {
+                let broken = validate(&paths);
+                paths.retain(|path| !broken.contains(path));
+            }
+            let seed = args.seed.unwrap_or_else(rand::random);
+            debug!("Seed {}", seed);
+            // rating seeding uses the ranking of the last run into the same folder by default
+            let seeding = match &args.seeding {
+                seeding::Seeding::Rating(None) => seeding::Seeding::Rating(Some(
+                    Path::new(&args.to_directory).join(export::RANKING_FILE).display().to_string()
+                )),
+                seeding => seeding.clone(),
+            };
+            let paths = match seeding::seed(paths, &seeding, seed) {
+                Ok(paths) => paths,
+                Err(message) => {
+                    eprintln!("{}", message);
+                    process::exit(1);
+                }
+            };
+            let mut tournament = tournament::Tournament::new(args.from_directory.clone(), paths, args.match_type);
+            tournament.seed = seed;
+            tournament.third_place = args.third_place;
+            if let Some(rounds) = args.rounds {
+                tournament.rounds = rounds;
+            }
+            tournament.rating_system = args.rating_system;
+            tournament.generate_round();
+            tournament
+        }
+    };
+
+    // a session that was over when it was saved has been exported back then already
+    let exported = args.resume.is_some() && tournament.done;
+    if exported {
+        println!("{} is over already, its winners were exported when it ended", session_path);
+    }
+
+    if !tournament.done {
+        match (&args.script, args.frontend) {
+            (Some(path), _) => play_script(&mut tournament, path, &session_path),
+            (None, Frontend::Window) => {
+                if let Err(message) = play_in_window(&mut tournament, &session_path, &args) {
+                    eprintln!("Could not open a window ({}), using the terminal instead", message);
+                    terminal::play(&mut tournament, &session_path, args.preview);
+                }
+            },
+            (None, Frontend::Terminal) => terminal::play(&mut tournament, &session_path, args.preview),
+        }
+    }
+
+    // only a finished tournament gets exported, escaping just closes the window
+    if tournament.done && !exported {
+        print!("{}", tournament.scoreboard());
+        let winners = tournament.winners(args.export.top);
+        match export::export(&winners, &args.to_directory, &args.export) {
+            Ok(count) => println!("Exported {} picture(s) to {}", count, args.to_directory),
+            Err(error) => {
+                eprintln!("Export to {} failed: {}", args.to_directory, error);
+                process::exit(1);
+            }
+        }
+        if let Err(error) = export::write_ranking(&tournament, &args.to_directory) {
+            eprintln!("Writing the ranking to {} failed: {}", args.to_directory, error);
+            process::exit(1);
+        }
+    }
+}
+
+// loads a saved session and checks which pictures came and went since it was saved
+fn resume_tournament(path: &str, args: &Args) -> Tournament {
+    let mut tournament = match session::load(path) {
+        Ok(tournament) => tournament,
+        Err(message) => {
+            eprintln!("{}", message);
             process::exit(1);
         }
     };
 
-    // debug!("{:};{:}", directory_path, category);
+    if tournament.category != args.match_type {
+        println!("Resuming a {} session, ignoring {}", tournament.category.to_arg(), args.match_type.to_arg());
+    }
+
+    let added = session::added_paths(&tournament, &args.scan);
+    if !added.is_empty() {
+        println!("{} picture(s) were added since the session was saved, they are not part of it:", added.len());
+        for path in added {
+            println!("  {}", path);
+        }
+    }
+
+    for player in session::removed_players(&tournament) {
+        println!("{} is gone, it forfeits all its games", tournament.paths[tournament.players[player].path_index]);
+        tournament.players[player].forfeit = true;
+    }
+    // they are already part of the session, so broken ones can only forfeit
+    if args.validate {
+        let present: Vec<String> = tournament.paths.iter()
+            .filter(|path| Path::new(path).exists())
+            .cloned()
+            .collect();
+        for path in validate(&present) {
+            tournament.forfeit(&path);
+        }
+    }
+    tournament.play_forfeits();
+    tournament
+}
 
-    let mut tournament = tournament::Tournament::new(directory_path, category);
-    tournament.generate_round();
+// opens every picture once and reports the ones that can't be, returns their paths
+fn validate(paths: &[String]) -> Vec<String> {
+    println!("Checking {} picture(s)", paths.len());
+    let broken = picture::validate(paths);
+    if !broken.is_empty() {
+        println!("{} picture(s) can't be opened:", broken.len());
+        for (path, error) in &broken {
+            println!("  {} ({})", path, error);
+        }
+    }
+    broken.into_iter().map(|(path, _)| path).collect()
+}
+
+// plays the decisions of a script without showing anything, an unfinished tournament is saved
+// so it can be resumed by hand
+fn play_script(tournament: &mut Tournament, path: &str, session_path: &str) {
+    let commands = match script::read(path) {
+        Ok(commands) => commands,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    };
+
+    let used = script::run(tournament, &commands);
+    save_session(tournament, session_path);
+    if used < commands.len() {
+        println!("The tournament was over after {} of {} script line(s)", used, commands.len());
+    }
+    if !tournament.done {
+        print!("{}", tournament.scoreboard());
+        println!("The script ended before the tournament was over, continue with --resume {}", session_path);
+    }
+}
 
-    let mut buffer = tournament.get_current_buffer();
+#[cfg(feature = "gui")]
+fn play_in_window(tournament: &mut Tournament, session_path: &str, args: &Args) -> Result<(), String> {
+    // Build the GUI in General
+    let (width, height) = args.window_size;
+    let mut window = draw::get_window(width, height, args.fullscreen)?;
+    window.limit_update_rate(Some(Duration::from_micros(16600)));
 
+    // decodes the next games while the current one is on screen
+    let previews = match args.preview_size {
+        0 => None,
+        size => Some(picture::Previews {
+            folder: Path::new(&args.to_directory).join(picture::PREVIEW_FOLDER),
+            size,
+            budget: picture::PREVIEW_BUDGET,
+        }),
+    };
+    let prefetcher = prefetch::Prefetcher::new(args.cache_size * 1024 * 1024, previews, args.filter);
+    // the buffer is drawn at the size of the window, so nothing gets stretched
+    let mut size = draw::window_size(&window);
+    // pictures of the game on screen that can't be opened, they are shown as a placeholder
+    let (mut buffer, mut broken) = show_game(tournament, &prefetcher, size, args.background);
+    // a new size and since when the window has it, while it is being dragged bigger or smaller
+    let mut resizing: Option<((u32, u32), Instant)> = None;
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
+    // the forfeits of broken pictures may finish the tournament without a decision
+    while window.is_open() && !window.is_key_down(Key::Escape) && !tournament.done {
         window.update_with_buffer(
             &buffer.bytes,
-            draw::BUFFER_WIDTH as usize,
-            draw::BUFFER_HEIGHT as usize
+            buffer.width as usize,
+            buffer.height as usize
         ).unwrap();
 
+        // until the window settles the old buffer is stretched, drawing every size in between
+        // would mean scaling the pictures again for each of them
+        let current = draw::window_size(&window);
+        if current != size && current.0 > 0 && current.1 > 0 {
+            match resizing {
+                Some((pending, since)) if pending == current => {
+                    if since.elapsed() >= RESIZE_DELAY {
+                        debug!("Window resized to {:?}", current);
+                        size = current;
+                        resizing = None;
+                        (buffer, broken) = render_game(tournament, &prefetcher, size, args.background);
+                    }
+                },
+                _ => resizing = Some((current, Instant::now())),
+            }
+        }
+
+        // a placeholder stays on screen until any key is pressed, then its picture forfeits and
+        // the next game comes up
+        if !broken.is_empty() {
+            if window.get_keys_pressed(KeyRepeat::No).is_some_and(|keys| !keys.is_empty()) {
+                for (path, _) in &broken {
+                    tournament.forfeit(path);
+                }
+                save_session(tournament, session_path);
+                if tournament.done {
+                    break;
+                }
+                (buffer, broken) = show_game(tournament, &prefetcher, size, args.background);
+            }
+            continue;
+        }
+
         // the user pressed a like/dislike key, so we want to save the result and load the next game
-        if window.is_key_pressed(Key::A, KeyRepeat::No) {
-            debug!("A Pressed!");
-            tournament.handle_key_press("left".to_string());
+        // W is a draw between both pictures, S no decision at all for now and X rejects both
+        let decision = KEY_DECISIONS.iter()
+            .find(|(key, _)| window.is_key_pressed(*key, KeyRepeat::No))
+            .map(|(_, decision)| *decision);
+        if let Some(decision) = decision {
+            debug!("{:?} Pressed!", decision);
+            tournament.submit(decision);
             debug!("Keypress Handled");
-            buffer = tournament.get_current_buffer();
+            save_session(tournament, session_path);
+            if tournament.done {
+                break;
+            }
+            (buffer, broken) = show_game(tournament, &prefetcher, size, args.background);
         }
-        if window.is_key_pressed(Key::D, KeyRepeat::No) {
-            debug!("D Pressed!");
-            tournament.handle_key_press("right".to_string());
-            debug!("Keypress Handled");
-            buffer = tournament.get_current_buffer();
+
+        // take back or repeat the latest decision
+        if window.is_key_pressed(Key::Backspace, KeyRepeat::No) || window.is_key_pressed(Key::Z, KeyRepeat::No) {
+            debug!("Undo Pressed!");
+            if tournament.undo() {
+                save_session(tournament, session_path);
+                (buffer, broken) = show_game(tournament, &prefetcher, size, args.background);
+            }
+        }
+        if window.is_key_pressed(Key::Y, KeyRepeat::No) {
+            debug!("Redo Pressed!");
+            if tournament.redo() {
+                save_session(tournament, session_path);
+                if tournament.done {
+                    break;
+                }
+                (buffer, broken) = show_game(tournament, &prefetcher, size, args.background);
+            }
         }
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+// draws a game that just came up, its latency starts now
+#[cfg(feature = "gui")]
+fn show_game(tournament: &mut Tournament, prefetcher: &prefetch::Prefetcher, size: (u32, u32), background: draw::Background) -> (draw::Buffer, Vec<(String, String)>) {
+    let (buffer, broken) = render_game(tournament, prefetcher, size, background);
+    for (path, error) in &broken {
+        eprintln!("Could not open {} ({}), it forfeits its remaining games", path, error);
+    }
+    tournament.mark_presented();
+    (buffer, broken)
+}
+
+// draws the current game, a picture that can't be opened is drawn as a placeholder with its name
+// and the error. Also returns those pictures.
+#[cfg(feature = "gui")]
+fn render_game(tournament: &Tournament, prefetcher: &prefetch::Prefetcher, size: (u32, u32), background: draw::Background) -> (draw::Buffer, Vec<(String, String)>) {
+    prefetcher.prefetch(tournament, size);
+    draw::buffer_from_game(tournament, prefetcher, size, background)
+}
+
+#[cfg(not(feature = "gui"))]
+fn play_in_window(_tournament: &mut Tournament, _session_path: &str, _args: &Args) -> Result<(), String> {
+    Err("built without the gui feature".to_string())
+}
+
+// a failed autosave is not worth stopping for, the user can keep going
+fn save_session(tournament: &Tournament, session_path: &str) {
+    if let Err(message) = session::save(tournament, session_path) {
+        eprintln!("{}", message);
+    }
+}
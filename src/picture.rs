@@ -0,0 +1,220 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use image::{DynamicImage, GenericImage, ImageDecoder, ImageError, ImageFormat, ImageResult};
+use image::{bmp, gif, hdr, ico, jpeg, png, ppm, tga, tiff, webp};
+use log::{debug};
+
+use super::resample::{self, Filter};
+
+// where the previews are kept, inside the destination folder next to the session
+pub const PREVIEW_FOLDER: &str = ".previews";
+// how much space the previews may take on disk by default
+pub const PREVIEW_BUDGET: u64 = 512 * 1024 * 1024;
+// the folder is checked against the budget every this many new previews
+const PRUNE_EVERY: usize = 64;
+
+// tells apart the temporary files of previews that are written at the same time
+static TEMPORARY: AtomicUsize = AtomicUsize::new(0);
+
+// opens a picture without taking the whole session down with it. The decoders of this image
+// version panic on some broken files instead of returning an error, so that is caught as well.
+pub fn open(path: &str) -> Result<DynamicImage, String> {
+    match panic::catch_unwind(|| image::open(path)) {
+        Ok(Ok(image)) => Ok(image),
+        Ok(Err(error)) => Err(error.to_string()),
+        Err(_) => Err("the decoder crashed".to_string()),
+    }
+}
+
+// width and height from the header of the file, without decoding the picture
+pub fn dimensions(path: &str) -> Result<(u32, u32), String> {
+    let extension = Path::new(path).extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let reader = BufReader::new(File::open(path).map_err(|error| error.to_string())?);
+    let read = panic::catch_unwind(panic::AssertUnwindSafe(|| match &extension[..] {
+        "jpg" | "jpeg" => header(jpeg::JPEGDecoder::new(reader)),
+        "png" => header(png::PNGDecoder::new(reader)),
+        "gif" => header(gif::Decoder::new(reader)),
+        "webp" => header(webp::WebpDecoder::new(reader)),
+        "tif" | "tiff" => tiff::TIFFDecoder::new(reader).and_then(header),
+        "tga" => header(tga::TGADecoder::new(reader)),
+        "bmp" => header(bmp::BMPDecoder::new(reader)),
+        "ico" => ico::ICODecoder::new(reader).and_then(header),
+        "hdr" => hdr::HDRAdapter::new(reader).and_then(header),
+        "ppm" => ppm::PPMDecoder::new(reader).and_then(header),
+        _ => Err(ImageError::UnsupportedError(format!("No decoder for .{}", extension))),
+    }));
+    match read {
+        Ok(Ok(dimensions)) => Ok(dimensions),
+        Ok(Err(error)) => Err(error.to_string()),
+        Err(_) => Err("the decoder crashed".to_string()),
+    }
+}
+
+fn header<D: ImageDecoder>(mut decoder: D) -> ImageResult<(u32, u32)> {
+    decoder.dimensions()
+}
+
+// decodes every picture once before the tournament starts, spread over all cores
+// returns the ones that can't be opened with the error, in the order of the paths
+pub fn validate(paths: &[String]) -> Vec<(String, String)> {
+    if paths.is_empty() {
+        return vec![];
+    }
+    let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+    let chunk_size = paths.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = paths.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || {
+                chunk.iter()
+                    .filter_map(|path| open(path).err().map(|error| (path.clone(), error)))
+                    .collect::<Vec<(String, String)>>()
+            }))
+            .collect();
+        workers.into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    })
+}
+
+// smaller copies of big pictures on disk, decoding a 40 MP jpeg takes a lot longer than a png
+// of screen size. A preview belongs to the path, modification time and size of its picture, so
+// a changed picture gets a new one. Once they take more than the budget the previews that were
+// used the longest time ago are deleted.
+#[derive(Debug, Clone)]
+pub struct Previews {
+    pub folder: PathBuf,
+    // longest side of a preview
+    pub size: u32,
+    // bytes on disk
+    pub budget: u64,
+}
+
+impl Previews {
+    // the preview of a picture, made from the full picture the first time
+    // pictures that are small already are used as they are
+    pub fn open(&self, path: &str) -> Result<DynamicImage, String> {
+        let preview_path = self.preview_path(path)?;
+        if preview_path.exists() {
+            match open(&preview_path.display().to_string()) {
+                Ok(preview) => {
+                    // the modification time tells prune when it was used last
+                    let _ = File::options().write(true).open(&preview_path)
+                        .and_then(|file| file.set_modified(SystemTime::now()));
+                    return Ok(preview);
+                },
+                Err(error) => debug!("Broken preview {} for {}: {}", preview_path.display(), path, error),
+            }
+        }
+
+        let image = open(path)?;
+        if image.width() <= self.size && image.height() <= self.size {
+            return Ok(image);
+        }
+        // the window scales it once more, so it is made as sharp as possible
+        let (width, height) = if image.width() >= image.height() {
+            (self.size, (image.height() as u64 * self.size as u64 / image.width() as u64) as u32)
+        } else {
+            ((image.width() as u64 * self.size as u64 / image.height() as u64) as u32, self.size)
+        };
+        let preview = DynamicImage::ImageRgba8(resample::resize(&image, width, height, Filter::Lanczos3));
+        // without a preview on disk it is simply made again next time
+        if let Err(error) = self.write(&preview, &preview_path) {
+            debug!("Could not write the preview for {}: {}", path, error);
+        }
+        Ok(preview)
+    }
+
+    fn preview_path(&self, path: &str) -> Result<PathBuf, String> {
+        let metadata = fs::metadata(path).map_err(|error| error.to_string())?;
+        let modified = metadata.modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos())
+            .unwrap_or(0);
+        let hash = stable_hash(&[
+            path.as_bytes(),
+            &modified.to_le_bytes(),
+            &metadata.len().to_le_bytes(),
+            &self.size.to_le_bytes(),
+        ]);
+        Ok(self.folder.join(format!("{:016x}.png", hash)))
+    }
+
+    // deletes the previews that were used the longest time ago until the rest fit into the
+    // budget, and temporary files a crash left behind
+    pub fn prune(&self) {
+        let entries = match fs::read_dir(&self.folder) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let mut previews = vec![];
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let metadata = match entry.metadata() {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            match path.extension().and_then(OsStr::to_str) {
+                Some("png") => previews.push((modified, metadata.len(), path)),
+                // one that is still being written is younger than a minute
+                Some("tmp") if modified.elapsed().is_ok_and(|age| age.as_secs() > 60) => {
+                    let _ = fs::remove_file(&path);
+                },
+                _ => {}
+            }
+        }
+
+        let mut used: u64 = previews.iter().map(|(_, bytes, _)| bytes).sum();
+        previews.sort();
+        for (_, bytes, path) in previews {
+            if used <= self.budget {
+                break;
+            }
+            debug!("Deleting the preview {}", path.display());
+            if fs::remove_file(&path).is_ok() {
+                used -= bytes;
+            }
+        }
+    }
+
+    // written next to its place first, so nobody ever reads half a preview
+    fn write(&self, preview: &DynamicImage, preview_path: &Path) -> Result<(), String> {
+        fs::create_dir_all(&self.folder).map_err(|error| error.to_string())?;
+        let number = TEMPORARY.fetch_add(1, Ordering::SeqCst);
+        if number.is_multiple_of(PRUNE_EVERY) {
+            self.prune();
+        }
+        let temporary = preview_path.with_extension(format!("{}.tmp", number));
+        let mut file = File::create(&temporary).map_err(|error| error.to_string())?;
+        let written = preview.save(&mut file, ImageFormat::PNG)
+            .map_err(|error| error.to_string())
+            .and_then(|_| fs::rename(&temporary, preview_path).map_err(|error| error.to_string()));
+        if written.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        written
+    }
+}
+
+// FNV-1a, unlike the hashers of the standard library it gives the same value with every version
+// of Rust, so the previews on disk stay valid across updates
+fn stable_hash(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.iter() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
@@ -0,0 +1,173 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use log::{debug};
+
+use image_viewer::picture;
+use image_viewer::picture::Previews;
+use image_viewer::resample::Filter;
+use image_viewer::tournament::Tournament;
+use super::cache::{Cache, Key, Scaled};
+use super::draw;
+
+// how many games, the current one included, are decoded ahead of time
+const GAMES_AHEAD: usize = 4;
+// decoding is mostly waiting for memory, more threads than this don't help
+const MAX_WORKERS: usize = 4;
+
+// decodes and scales the pictures of the upcoming games on worker threads, so the next game
+// shows up right away. Everything ends up in the cache, so pictures that play again are ready too.
+pub struct Prefetcher {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+    // read from the file headers for the layout, only ever used on the main thread
+    dimensions: Mutex<HashMap<String, (u32, u32)>>,
+}
+
+struct Shared {
+    // none decodes the full pictures every time
+    previews: Option<Previews>,
+    filter: Filter,
+    state: Mutex<State>,
+    // signalled when there is a new job or a job is done
+    changed: Condvar,
+}
+
+struct State {
+    // what the upcoming games need, most urgent first, the cache keeps these no matter what
+    wanted: Vec<Key>,
+    queue: VecDeque<Key>,
+    // being decoded right now
+    working: HashSet<Key>,
+    cache: Cache,
+    stop: bool,
+}
+
+impl Prefetcher {
+    // the cache may use about budget bytes
+    pub fn new(budget: usize, previews: Option<Previews>, filter: Filter) -> Prefetcher {
+        let shared = Arc::new(Shared {
+            previews,
+            filter,
+            state: Mutex::new(State {
+                wanted: vec![],
+                queue: VecDeque::new(),
+                working: HashSet::new(),
+                cache: Cache::new(budget),
+                stop: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let count = thread::available_parallelism()
+            .map(|threads| threads.get().saturating_sub(1))
+            .unwrap_or(1)
+            .clamp(1, MAX_WORKERS);
+        let workers = (0..count)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || work(&shared))
+            })
+            .collect();
+        Prefetcher { shared, workers, dimensions: Mutex::new(HashMap::new()) }
+    }
+
+    // queues the pictures of the current and the next games that aren't cached yet, for a
+    // window of the given size
+    pub fn prefetch(&self, tournament: &Tournament, size: (u32, u32)) {
+        let mut wanted: Vec<Key> = vec![];
+        for (home, guest) in tournament.upcoming_pairs(GAMES_AHEAD) {
+            let slots = draw::layout(size, self.dimensions(home), guest.map(|guest| self.dimensions(guest)));
+            for (path, slot) in std::iter::once(home).chain(guest).zip(slots) {
+                wanted.push((path.to_string(), (slot.width, slot.height)));
+            }
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        let queue = wanted.iter()
+            .filter(|key| !state.cache.contains(key) && !state.working.contains(*key))
+            .cloned()
+            .collect();
+        state.queue = queue;
+        state.wanted = wanted;
+        debug!("Prefetching {} picture(s)", state.queue.len());
+        self.shared.changed.notify_all();
+    }
+
+    // the picture scaled into a slot of the given size, waits for it if it is not ready yet
+    pub fn get(&self, path: &str, size: (u32, u32)) -> Scaled {
+        let key = (path.to_string(), size);
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(scaled) = state.cache.get(&key) {
+                return scaled;
+            }
+            if !state.working.contains(&key) {
+                break;
+            }
+            state = self.shared.changed.wait(state).unwrap();
+        }
+        // nobody is on it, e.g. because it was never asked for
+        state.queue.retain(|queued| *queued != key);
+        drop(state);
+        debug!("Prefetch miss for {}", path);
+        let scaled = load(&key, &self.shared);
+        let mut state = self.shared.state.lock().unwrap();
+        let State { cache, wanted, .. } = &mut *state;
+        cache.insert(key, scaled.clone(), wanted);
+        scaled
+    }
+
+    // width and height of a picture without decoding it. One that can't be read is laid out
+    // like a usual photo, it only shows up as a placeholder anyway.
+    pub fn dimensions(&self, path: &str) -> (u32, u32) {
+        *self.dimensions.lock().unwrap()
+            .entry(path.to_string())
+            .or_insert_with(|| picture::dimensions(path).unwrap_or((3, 2)))
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stop = true;
+        self.shared.changed.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let key = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.stop {
+                    return;
+                }
+                if let Some(key) = state.queue.pop_front() {
+                    state.working.insert(key.clone());
+                    break key;
+                }
+                state = shared.changed.wait(state).unwrap();
+            }
+        };
+
+        let scaled = load(&key, shared);
+
+        let mut state = shared.state.lock().unwrap();
+        state.working.remove(&key);
+        let State { cache, wanted, .. } = &mut *state;
+        cache.insert(key, scaled, wanted);
+        shared.changed.notify_all();
+    }
+}
+
+fn load((path, (width, height)): &Key, shared: &Shared) -> Scaled {
+    // a preview smaller than the slot would look blurry, e.g. full screen on a big monitor
+    let image = match &shared.previews {
+        Some(previews) if previews.size >= *width.max(height) => previews.open(path)?,
+        _ => picture::open(path)?,
+    };
+    Ok(Arc::new(draw::scale(&image, *width, *height, shared.filter)))
+}
@@ -0,0 +1,234 @@
+use std::f64::consts::PI;
+
+// glicko-2 works on a different scale than the displayed rating, this converts between both
+const GLICKO2_SCALE: f64 = 173.7178;
+// precision of the volatility iteration
+const GLICKO2_EPSILON: f64 = 0.000001;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Rating {
+    pub value: f64,
+    // how sure we are about the value, only glicko-2 tracks it
+    pub deviation: f64,
+    // how erratic the player's results are, only glicko-2 tracks it
+    pub volatility: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating {
+            value: 1500.0,
+            deviation: 350.0,
+            volatility: 0.06,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum RatingSystem {
+    // k is the maximum amount of points that change hands in one game
+    Elo { k: f64 },
+    // tau constrains how fast the volatility may change
+    Glicko2 { tau: f64 },
+}
+
+impl Default for RatingSystem {
+    fn default() -> RatingSystem {
+        RatingSystem::Glicko2 { tau: 0.5 }
+    }
+}
+
+impl RatingSystem {
+    // accepts "elo", "elo:<k>", "glicko2" and "glicko2:<tau>"
+    pub fn from_arg(arg: &str) -> Result<RatingSystem, &'static str> {
+        let mut parts = arg.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let parameter = match parts.next() {
+            // K and tau scale every rating change, nothing but a positive number makes sense
+            Some(value) => match value.parse::<f64>() {
+                Ok(value) if value.is_finite() && value > 0.0 => Some(value),
+                _ => return Err("Rating system parameter must be a positive number"),
+            },
+            None => None,
+        };
+
+        match name {
+            "elo" => Ok(RatingSystem::Elo { k: parameter.unwrap_or(32.0) }),
+            "glicko2" => Ok(RatingSystem::Glicko2 { tau: parameter.unwrap_or(0.5) }),
+            _ => Err("Unrecognized rating system"),
+        }
+    }
+
+    // the inverse of from_arg
+    pub fn to_arg(self) -> String {
+        match self {
+            RatingSystem::Elo { k } => format!("elo:{}", k),
+            RatingSystem::Glicko2 { tau } => format!("glicko2:{}", tau),
+        }
+    }
+
+    // elo has no notion of how certain a rating is
+    pub fn has_deviation(&self) -> bool {
+        match self {
+            RatingSystem::Elo { .. } => false,
+            RatingSystem::Glicko2 { .. } => true,
+        }
+    }
+
+    // probability that home wins against guest
+    pub fn expected(&self, home: &Rating, guest: &Rating) -> f64 {
+        match self {
+            RatingSystem::Elo { .. } => {
+                1.0 / (1.0 + 10_f64.powf((guest.value - home.value) / 400.0))
+            },
+            RatingSystem::Glicko2 { .. } => {
+                let mu = (home.value - 1500.0) / GLICKO2_SCALE;
+                let mu_opponent = (guest.value - 1500.0) / GLICKO2_SCALE;
+                let phi_opponent = guest.deviation / GLICKO2_SCALE;
+                glicko2_expected(mu, mu_opponent, phi_opponent)
+            }
+        }
+    }
+
+    // rates a single game, score_home is 1 for a home win, 0 for a guest win and 0.5 for a draw
+    pub fn rate(&self, home: Rating, guest: Rating, score_home: f64) -> (Rating, Rating) {
+        match self {
+            RatingSystem::Elo { k } => {
+                let expected_home = self.expected(&home, &guest);
+                let change = k * (score_home - expected_home);
+                (
+                    Rating { value: home.value + change, ..home },
+                    Rating { value: guest.value - change, ..guest },
+                )
+            },
+            RatingSystem::Glicko2 { tau } => {
+                // every game is treated as its own rating period
+                (
+                    glicko2_rate(home, &[(guest, score_home)], *tau),
+                    glicko2_rate(guest, &[(home, 1.0 - score_home)], *tau),
+                )
+            }
+        }
+    }
+}
+
+fn glicko2_g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
+
+fn glicko2_expected(mu: f64, mu_opponent: f64, phi_opponent: f64) -> f64 {
+    1.0 / (1.0 + (-glicko2_g(phi_opponent) * (mu - mu_opponent)).exp())
+}
+
+// step by step implementation of http://www.glicko.net/glicko/glicko2.pdf for the games of one
+// rating period, each with the opponent and the score of the player
+fn glicko2_rate(player: Rating, games: &[(Rating, f64)], tau: f64) -> Rating {
+    let mu = (player.value - 1500.0) / GLICKO2_SCALE;
+    let phi = player.deviation / GLICKO2_SCALE;
+
+    let mut information = 0.0;
+    let mut improvement = 0.0;
+    for (opponent, score) in games {
+        let mu_opponent = (opponent.value - 1500.0) / GLICKO2_SCALE;
+        let phi_opponent = opponent.deviation / GLICKO2_SCALE;
+        let g = glicko2_g(phi_opponent);
+        let expected = glicko2_expected(mu, mu_opponent, phi_opponent);
+        information += g * g * expected * (1.0 - expected);
+        improvement += g * (score - expected);
+    }
+    let variance = 1.0 / information;
+    let delta = variance * improvement;
+
+    // find the new volatility with the illinois algorithm
+    let a = (player.volatility * player.volatility).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        let denominator = phi * phi + variance + ex;
+        ex * (delta * delta - phi * phi - variance - ex) / (2.0 * denominator * denominator)
+            - (x - a) / (tau * tau)
+    };
+
+    let mut lower = a;
+    let mut upper = if delta * delta > phi * phi + variance {
+        (delta * delta - phi * phi - variance).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * tau) < 0.0 {
+            k += 1.0;
+        }
+        a - k * tau
+    };
+
+    let mut f_lower = f(lower);
+    let mut f_upper = f(upper);
+    while (upper - lower).abs() > GLICKO2_EPSILON {
+        let candidate = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+        let f_candidate = f(candidate);
+        if f_candidate * f_upper <= 0.0 {
+            lower = upper;
+            f_lower = f_upper;
+        } else {
+            f_lower /= 2.0;
+        }
+        upper = candidate;
+        f_upper = f_candidate;
+    }
+    let volatility = (lower / 2.0).exp();
+
+    let phi_star = (phi * phi + volatility * volatility).sqrt();
+    let phi_new = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / variance).sqrt();
+    let mu_new = mu + phi_new * phi_new * improvement;
+
+    Rating {
+        value: mu_new * GLICKO2_SCALE + 1500.0,
+        deviation: phi_new * GLICKO2_SCALE,
+        volatility,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glicko2_matches_the_example_of_the_paper() {
+        let player = Rating { value: 1500.0, deviation: 200.0, volatility: 0.06 };
+        let games = [
+            (Rating { value: 1400.0, deviation: 30.0, volatility: 0.06 }, 1.0),
+            (Rating { value: 1550.0, deviation: 100.0, volatility: 0.06 }, 0.0),
+            (Rating { value: 1700.0, deviation: 300.0, volatility: 0.06 }, 0.0),
+        ];
+        let rating = glicko2_rate(player, &games, 0.5);
+        assert!((rating.value - 1464.06).abs() < 0.01, "{:?}", rating);
+        assert!((rating.deviation - 151.52).abs() < 0.01, "{:?}", rating);
+        assert!((rating.volatility - 0.05999).abs() < 0.00001, "{:?}", rating);
+    }
+
+    #[test]
+    fn elo_moves_the_same_points_both_ways() {
+        let system = RatingSystem::Elo { k: 32.0 };
+        let (home, guest) = system.rate(Rating::default(), Rating::default(), 1.0);
+        assert_eq!(home.value, 1516.0);
+        assert_eq!(guest.value, 1484.0);
+    }
+
+    #[test]
+    fn rating_system_from_arg() {
+        assert!(matches!(RatingSystem::from_arg("elo"), Ok(RatingSystem::Elo { k }) if k == 32.0));
+        assert!(matches!(RatingSystem::from_arg("elo:16"), Ok(RatingSystem::Elo { k }) if k == 16.0));
+        assert!(matches!(RatingSystem::from_arg("glicko2:0.3"), Ok(RatingSystem::Glicko2 { tau }) if tau == 0.3));
+        assert!(RatingSystem::from_arg("elo:x").is_err());
+        for parameter in ["0", "-16", "NaN", "inf", "-inf"] {
+            assert!(RatingSystem::from_arg(&format!("elo:{}", parameter)).is_err(), "{}", parameter);
+            assert!(RatingSystem::from_arg(&format!("glicko2:{}", parameter)).is_err(), "{}", parameter);
+        }
+        assert!(RatingSystem::from_arg("chess").is_err());
+    }
+
+    #[test]
+    fn rating_system_arg_round_trip() {
+        for arg in ["elo:16", "glicko2:0.3"] {
+            assert_eq!(RatingSystem::from_arg(arg).unwrap().to_arg(), arg);
+        }
+    }
+}
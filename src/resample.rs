@@ -0,0 +1,213 @@
+use std::sync::OnceLock;
+use image::{DynamicImage, FilterType, ImageBuffer, Rgba, RgbaImage};
+use image::imageops;
+
+// 16 bits per channel, so the dark tones don't band once they are linear
+type LinearImage = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+// how pictures are scaled, sharper filters show the difference in sharpness between two
+// pictures better but take longer
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    #[default]
+    Lanczos3,
+}
+
+impl Filter {
+    pub fn from_arg(arg: &str) -> Result<Filter, &'static str> {
+        match arg {
+            "nearest" => Ok(Filter::Nearest),
+            "triangle" => Ok(Filter::Triangle),
+            "catmull-rom" => Ok(Filter::CatmullRom),
+            "lanczos3" => Ok(Filter::Lanczos3),
+            _ => Err("Unrecognized filter"),
+        }
+    }
+
+    fn filter_type(self) -> FilterType {
+        match self {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+// scales a picture to exactly width x height. Averaging sRGB values darkens fine detail, so
+// everything but nearest is done on linear light, premultiplied so the invisible colour of
+// transparent pixels doesn't bleed into the edges next to them. Big reductions are first boxed down to about
+// twice the size, the filter then only has a few pixels to look at for each one.
+pub fn resize(image: &DynamicImage, width: u32, height: u32, filter: Filter) -> RgbaImage {
+    let width = width.max(1);
+    let height = height.max(1);
+    if filter == Filter::Nearest {
+        return imageops::resize(&image.to_rgba(), width, height, FilterType::Nearest);
+    }
+
+    let mut linear = to_linear(&image.to_rgba());
+    let factor = (linear.width() / (width * 2)).min(linear.height() / (height * 2));
+    if factor >= 2 {
+        linear = box_reduce(&linear, factor);
+    }
+    let resized = imageops::resize(&linear, width, height, filter.filter_type());
+    to_srgb(&resized)
+}
+
+// averages factor x factor blocks, a leftover edge that doesn't fill a block is dropped
+fn box_reduce(image: &LinearImage, factor: u32) -> LinearImage {
+    let width = image.width() / factor;
+    let height = image.height() / factor;
+    let count = factor * factor;
+    let mut reduced = ImageBuffer::new(width, height);
+    let mut sums = vec![[0u32; 4]; width as usize];
+    for y in 0..height {
+        for sum in sums.iter_mut() {
+            *sum = [0; 4];
+        }
+        for row in y * factor..(y + 1) * factor {
+            for x in 0..width * factor {
+                let pixel = image.get_pixel(x, row);
+                let sum = &mut sums[(x / factor) as usize];
+                for channel in 0..4 {
+                    sum[channel] += pixel[channel] as u32;
+                }
+            }
+        }
+        for (x, sum) in sums.iter().enumerate() {
+            let average = |channel: usize| (sum[channel] / count) as u16;
+            reduced.put_pixel(x as u32, y, Rgba([average(0), average(1), average(2), average(3)]));
+        }
+    }
+    reduced
+}
+
+// premultiplied by alpha
+fn to_linear(image: &RgbaImage) -> LinearImage {
+    let table = linear_table();
+    let linear = image.chunks(4)
+        .flat_map(|pixel| {
+            let alpha = pixel[3] as u32;
+            let premultiply = |value: u8| ((table[value as usize] as u32 * alpha + 127) / 255) as u16;
+            // alpha is linear already
+            [premultiply(pixel[0]), premultiply(pixel[1]), premultiply(pixel[2]), alpha as u16 * 257]
+        })
+        .collect();
+    ImageBuffer::from_raw(image.width(), image.height(), linear).unwrap()
+}
+
+// back to straight alpha, the filters can overshoot so a colour may come out brighter than its
+// alpha allows
+fn to_srgb(image: &LinearImage) -> RgbaImage {
+    let table = srgb_table();
+    let srgb = image.chunks(4)
+        .flat_map(|pixel| {
+            let alpha = pixel[3] as u32;
+            let unpremultiply = |value: u16| match alpha {
+                0 => 0,
+                _ => table[((value as u32 * 65535 + alpha / 2) / alpha).min(65535) as usize >> 4],
+            };
+            [unpremultiply(pixel[0]), unpremultiply(pixel[1]), unpremultiply(pixel[2]), ((alpha + 128) / 257) as u8]
+        })
+        .collect();
+    ImageBuffer::from_raw(image.width(), image.height(), srgb).unwrap()
+}
+
+// sRGB to linear for every 8 bit value
+fn linear_table() -> &'static [u16; 256] {
+    static TABLE: OnceLock<[u16; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0; 256];
+        for (value, linear) in table.iter_mut().enumerate() {
+            let value = value as f64 / 255.0;
+            let light = if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            };
+            *linear = (light * 65535.0).round() as u16;
+        }
+        table
+    })
+}
+
+// linear back to sRGB, the top 12 bits are plenty for 8 bits of output
+fn srgb_table() -> &'static [u8; 4096] {
+    static TABLE: OnceLock<[u8; 4096]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0; 4096];
+        for (index, srgb) in table.iter_mut().enumerate() {
+            // the middle of the values that share this index
+            let light = (index as f64 + 0.5) / 4096.0;
+            let value = if light <= 0.0031308 {
+                light * 12.92
+            } else {
+                1.055 * light.powf(1.0 / 2.4) - 0.055
+            };
+            *srgb = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+        table
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // columns of 4 x 4 pixels alternating between the two colours
+    fn stripes(first: [u8; 4], second: [u8; 4]) -> DynamicImage {
+        let image = RgbaImage::from_fn(4, 4, |x, _| Rgba(if x % 2 == 0 { first } else { second }));
+        DynamicImage::ImageRgba8(image)
+    }
+
+    #[test]
+    fn black_and_white_average_to_half_the_light() {
+        let image = stripes([0, 0, 0, 255], [255, 255, 255, 255]);
+        for filter in [Filter::Triangle, Filter::CatmullRom, Filter::Lanczos3] {
+            let pixel = *resize(&image, 1, 1, filter).get_pixel(0, 0);
+            // half the light is 188 in sRGB, averaging the sRGB values would give 128
+            assert!((187..=189).contains(&pixel[0]), "{:?}: {:?}", filter, pixel);
+            assert_eq!(pixel[0], pixel[2]);
+            assert_eq!(pixel[3], 255);
+        }
+    }
+
+    #[test]
+    fn transparent_pixels_dont_colour_their_neighbours() {
+        let image = stripes([255, 255, 255, 255], [255, 0, 0, 0]);
+        let pixel = *resize(&image, 1, 1, Filter::Triangle).get_pixel(0, 0);
+        assert_eq!([pixel[0], pixel[1], pixel[2]], [255, 255, 255]);
+        assert!((127..=128).contains(&pixel[3]), "{:?}", pixel);
+    }
+
+    #[test]
+    fn box_reduce_averages_blocks_and_drops_the_edge() {
+        // 5 x 5, the last row and column don't fill a block of 2
+        let mut image = LinearImage::new(5, 5);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let value = (x * 1000 + y * 100) as u16;
+            *pixel = Rgba([value, value, value, 65535]);
+        }
+        let reduced = box_reduce(&image, 2);
+        assert_eq!(reduced.dimensions(), (2, 2));
+        assert_eq!(reduced.get_pixel(0, 0)[0], 550);
+        assert_eq!(reduced.get_pixel(1, 0)[0], 2550);
+        assert_eq!(reduced.get_pixel(0, 1)[0], 750);
+        assert_eq!(reduced.get_pixel(1, 1)[3], 65535);
+    }
+
+    #[test]
+    fn big_reductions_end_up_at_the_wanted_size() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(101, 37, Rgba([200, 100, 50, 255])));
+        let resized = resize(&image, 10, 3, Filter::Lanczos3);
+        assert_eq!(resized.dimensions(), (10, 3));
+        // a flat colour stays the same colour
+        let pixel = resized.get_pixel(5, 1);
+        for (channel, value) in [200, 100, 50, 255].iter().enumerate() {
+            assert!((pixel[channel] as i32 - value).abs() <= 1, "{:?}", pixel);
+        }
+    }
+}
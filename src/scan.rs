@@ -0,0 +1,193 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// everything image::open can decode, it goes by the extension as well
+pub const EXTENSIONS: [&str; 12] = ["bmp", "gif", "hdr", "ico", "jpeg", "jpg", "png", "ppm", "tga", "tif", "tiff", "webp"];
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    // descend into sub folders
+    pub recursive: bool,
+    // globs a picture has to match one of, every picture if there are none
+    pub include: Vec<String>,
+    // globs for pictures and folders to leave out
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub paths: Vec<String>,
+    // everything that was left out, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+// paths of all pictures in the directory, sorted so every run sees them in the same order
+pub fn scan(directory: &str, options: &ScanOptions) -> io::Result<Scan> {
+    let mut scan = Scan::default();
+    walk(Path::new(directory), Path::new(directory), options, &mut scan)?;
+    scan.paths.sort();
+    scan.skipped.sort();
+    Ok(scan)
+}
+
+fn walk(root: &Path, folder: &Path, options: &ScanOptions, scan: &mut Scan) -> io::Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let path = entry.path();
+        let display = path.display().to_string();
+        // globs always use / no matter the system
+        let relative = path.strip_prefix(root).unwrap_or(&path)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("/");
+
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            scan.skipped.push((display, "hidden".to_string()));
+            continue;
+        }
+        if options.exclude.iter().any(|pattern| glob_matches(pattern, &relative)) {
+            scan.skipped.push((display, "excluded".to_string()));
+            continue;
+        }
+
+        // symbolic links to folders are not followed, they could go round in circles
+        if entry.file_type()?.is_dir() {
+            if !options.recursive {
+                scan.skipped.push((display, "a folder, see --recursive".to_string()));
+            } else if let Err(error) = walk(root, &path, options, scan) {
+                scan.skipped.push((display, format!("unreadable: {}", error)));
+            }
+            continue;
+        }
+
+        if !is_picture(&path) {
+            scan.skipped.push((display, "not a picture".to_string()));
+            continue;
+        }
+        if !options.include.is_empty() && !options.include.iter().any(|pattern| glob_matches(pattern, &relative)) {
+            scan.skipped.push((display, "not included".to_string()));
+            continue;
+        }
+        scan.paths.push(display);
+    }
+    Ok(())
+}
+
+pub fn is_picture(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| EXTENSIONS.contains(&&extension[..]))
+}
+
+// matches a glob against a path relative to the scanned folder. * is anything but a /, ** is
+// anything at all, ? is a single character and [...] one of a set like [abc], [a-z] or [!0-9].
+// A glob without a / only looks at the file name, like in .gitignore.
+pub fn glob_matches(pattern: &str, relative: &str) -> bool {
+    let target = if pattern.contains('/') {
+        relative
+    } else {
+        relative.rsplit('/').next().unwrap_or(relative)
+    };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let target: Vec<char> = target.chars().collect();
+    matches(&pattern, &target)
+}
+
+fn matches(pattern: &[char], target: &[char]) -> bool {
+    match pattern.first() {
+        None => target.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            // "**/" may also stand for no folder at all
+            let rest = &pattern[2..];
+            if rest.first() == Some(&'/') && matches(&rest[1..], target) {
+                return true;
+            }
+            (0..=target.len()).any(|skip| matches(rest, &target[skip..]))
+        },
+        Some('*') => {
+            for skip in 0..=target.len() {
+                if matches(&pattern[1..], &target[skip..]) {
+                    return true;
+                }
+                if target.get(skip) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        },
+        Some('?') => {
+            target.first().is_some_and(|character| *character != '/') && matches(&pattern[1..], &target[1..])
+        },
+        Some('[') => {
+            // without a closing bracket it is just a character
+            let close = match pattern.iter().skip(2).position(|character| *character == ']') {
+                Some(position) => position + 2,
+                None => return target.first() == Some(&'[') && matches(&pattern[1..], &target[1..]),
+            };
+            let character = match target.first() {
+                Some(character) if *character != '/' => *character,
+                _ => return false,
+            };
+            let mut set = &pattern[1..close];
+            let negated = set.first() == Some(&'!') || set.first() == Some(&'^');
+            if negated {
+                set = &set[1..];
+            }
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    found |= set[i] <= character && character <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == character;
+                    i += 1;
+                }
+            }
+            found != negated && matches(&pattern[close + 1..], &target[1..])
+        },
+        Some(character) => target.first() == Some(character) && matches(&pattern[1..], &target[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_without_slash_looks_at_the_file_name() {
+        assert!(glob_matches("*.jpg", "a.jpg"));
+        assert!(glob_matches("*.jpg", "holiday/a.jpg"));
+        assert!(!glob_matches("*.jpg", "a.png"));
+        assert!(!glob_matches("holiday", "holiday/a.jpg"));
+    }
+
+    #[test]
+    fn glob_star_stays_inside_a_folder() {
+        assert!(glob_matches("holiday/*.jpg", "holiday/a.jpg"));
+        assert!(!glob_matches("holiday/*.jpg", "holiday/2020/a.jpg"));
+        assert!(!glob_matches("*/a.jpg", "a.jpg"));
+    }
+
+    #[test]
+    fn glob_double_star_crosses_folders() {
+        assert!(glob_matches("**/a.jpg", "a.jpg"));
+        assert!(glob_matches("**/a.jpg", "holiday/2020/a.jpg"));
+        assert!(glob_matches("holiday/**", "holiday/2020/a.jpg"));
+        assert!(!glob_matches("holiday/**/a.jpg", "work/a.jpg"));
+    }
+
+    #[test]
+    fn glob_question_mark_and_sets() {
+        assert!(glob_matches("img_?.png", "img_1.png"));
+        assert!(!glob_matches("img_?.png", "img_12.png"));
+        assert!(glob_matches("img_[0-9].png", "img_7.png"));
+        assert!(!glob_matches("img_[!0-9].png", "img_7.png"));
+        assert!(glob_matches("img_[abc].png", "img_b.png"));
+        assert!(!glob_matches("a?b", "a/b"));
+        // without a closing bracket [ is just a character
+        assert!(glob_matches("[a", "[a"));
+    }
+}
@@ -0,0 +1,74 @@
+use std::fs;
+use std::io;
+use std::io::Read;
+use log::{debug};
+
+use super::tournament::{Decision, Tournament};
+
+// one line of a script: a decision as in Decision::from_arg, "undo" or "redo"
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Command {
+    Decide(Decision),
+    Undo,
+    Redo,
+}
+
+impl Command {
+    pub fn from_arg(arg: &str) -> Result<Command, &'static str> {
+        match arg {
+            "undo" => Ok(Command::Undo),
+            "redo" => Ok(Command::Redo),
+            _ => Ok(Command::Decide(Decision::from_arg(arg)?)),
+        }
+    }
+}
+
+// reads a script from a file, or from stdin if the path is "-"
+pub fn read(path: &str) -> Result<Vec<Command>, String> {
+    let mut text = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut text)
+            .map_err(|error| format!("Could not read the script from stdin: {}", error))?;
+    } else {
+        text = fs::read_to_string(path)
+            .map_err(|error| format!("Could not read script {}: {}", path, error))?;
+    }
+    parse(&text)
+}
+
+// one command per line, empty lines and everything after a # are ignored
+pub fn parse(text: &str) -> Result<Vec<Command>, String> {
+    let mut commands = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let command = Command::from_arg(&line.to_lowercase())
+            .map_err(|message| format!("Line {}: {} '{}'", number + 1, message, line))?;
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+// plays the commands until the tournament is finished, returns how many were used
+pub fn run(tournament: &mut Tournament, commands: &[Command]) -> usize {
+    let mut used = 0;
+    for command in commands {
+        if tournament.is_finished() {
+            break;
+        }
+        debug!("Script: {:?}", command);
+        match command {
+            Command::Decide(decision) => tournament.submit(*decision),
+            Command::Undo => {
+                tournament.undo();
+            },
+            Command::Redo => {
+                tournament.redo();
+            }
+        }
+        used += 1;
+    }
+    used
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use super::export;
+
+// the order the pictures enter the tournament in. Knockouts pair neighbours in the first round,
+// so this decides who meets whom early.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Seeding {
+    // shuffled with the tournament's seed
+    Random,
+    // oldest first, so burst shots of the same scene meet each other right away
+    Date,
+    // by the rating in a ranking.csv of an earlier run, best against worst
+    // none means the ranking.csv in the destination directory
+    Rating(Option<String>),
+    // as the files are named
+    Sequential,
+}
+
+impl Seeding {
+    // accepts "random", "date", "rating", "rating:<ranking.csv>" and "sequential"
+    pub fn from_arg(arg: &str) -> Result<Seeding, &'static str> {
+        let mut parts = arg.splitn(2, ':');
+        match (parts.next().unwrap_or(""), parts.next()) {
+            ("random", None) => Ok(Seeding::Random),
+            ("date", None) => Ok(Seeding::Date),
+            ("rating", ranking) => Ok(Seeding::Rating(ranking.map(|ranking| ranking.to_string()))),
+            ("sequential", None) => Ok(Seeding::Sequential),
+            _ => Err("Unrecognized seeding"),
+        }
+    }
+}
+
+// puts the paths into the order of the seeding
+pub fn seed(mut paths: Vec<String>, seeding: &Seeding, seed: u64) -> Result<Vec<String>, String> {
+    match seeding {
+        Seeding::Random => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            paths.shuffle(&mut rng);
+        },
+        Seeding::Date => {
+            // pictures without a date keep their place at the end
+            paths.sort_by_key(|path| {
+                let date = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+                (date.is_none(), date)
+            });
+        },
+        Seeding::Rating(ranking) => {
+            let ranking = ranking.as_ref().ok_or("No ranking.csv to seed by rating")?;
+            let ratings = export::read_ratings(ranking)
+                .map_err(|error| format!("Could not read ratings from {}: {}", ranking, error))?;
+            let rating = |path: &String| {
+                // the pictures may have been copied somewhere else since, so the name is enough
+                ratings.iter()
+                    .find(|(rated, _)| rated == path)
+                    .or_else(|| ratings.iter().find(|(rated, _)| Path::new(rated).file_name() == Path::new(path).file_name()))
+                    .map(|(_, rating)| *rating)
+            };
+            // unrated pictures are the weakest seeds
+            let mut rated: Vec<(Option<f64>, String)> = paths.into_iter().map(|path| (rating(&path), path)).collect();
+            rated.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+            paths = bracket_order(rated.len()).into_iter().map(|seed| rated[seed].1.clone()).collect();
+        },
+        Seeding::Sequential => {}
+    }
+    Ok(paths)
+}
+
+// the classic bracket: 1 meets the last seed, and 1 and 2 can only meet in the final
+// for 8 players that is 1 8 4 5 2 7 3 6, zero based here. Seeds that don't exist are left out.
+fn bracket_order(count: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < count {
+        let size = order.len() * 2;
+        order = order.iter().flat_map(|&seed| vec![seed, size - 1 - seed]).collect();
+    }
+    order.into_iter().filter(|&seed| seed < count).collect()
+}
@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use rustc_serialize::json::{Json, ToJson};
+
+use super::rating::{Rating, RatingSystem};
+use super::scan;
+use super::sort::InsertionSort;
+use super::tournament::{Decision, Game, GameResult, Player, Tournament, TournamentType};
+
+// name of the session file inside the destination directory
+pub const SESSION_FILE: &str = "session.json";
+
+// writes the whole tournament to a json file
+// the file is written next to the target and then renamed, so a crash never leaves half a session
+pub fn save(tournament: &Tournament, path: &str) -> Result<(), String> {
+    let temporary = format!("{}.tmp", path);
+    if let Some(directory) = Path::new(path).parent() {
+        fs::create_dir_all(directory).map_err(|error| format!("Could not create {}: {}", directory.display(), error))?;
+    }
+    fs::write(&temporary, tournament.to_json().to_string())
+        .and_then(|_| fs::rename(&temporary, path))
+        .map_err(|error| format!("Could not save session to {}: {}", path, error))
+}
+
+pub fn load(path: &str) -> Result<Tournament, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|error| format!("Could not read session {}: {}", path, error))?;
+    let json = Json::from_str(&content)
+        .map_err(|error| format!("Session {} is not valid json: {}", path, error))?;
+    tournament_from_json(&json)
+}
+
+// pictures that were added to the folder since the session was saved, found the same way as
+// the pictures that are part of it
+pub fn added_paths(tournament: &Tournament, options: &scan::ScanOptions) -> Vec<String> {
+    scan::scan(&tournament.directory, options)
+        .map(|scan| scan.paths)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|path| !tournament.paths.contains(path))
+        .collect()
+}
+
+// players whose picture was removed since the session was saved
+pub fn removed_players(tournament: &Tournament) -> Vec<usize> {
+    (0..tournament.players.len())
+        .filter(|i| !Path::new(&tournament.paths[tournament.players[*i].path_index]).exists())
+        .collect()
+}
+
+impl ToJson for Rating {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("value".to_string(), self.value.to_json());
+        object.insert("deviation".to_string(), self.deviation.to_json());
+        object.insert("volatility".to_string(), self.volatility.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for Player {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("path_index".to_string(), self.path_index.to_json());
+        object.insert("is_in".to_string(), self.is_in.to_json());
+        object.insert("score".to_string(), self.score.to_json());
+        object.insert("byes".to_string(), self.byes.to_json());
+        object.insert("losses".to_string(), self.losses.to_json());
+        object.insert("rating".to_string(), self.rating.to_json());
+        object.insert("forfeit".to_string(), self.forfeit.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for Game {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("player_home".to_string(), self.player_home.to_json());
+        object.insert("player_guest".to_string(), self.player_guest.to_json());
+        object.insert("round".to_string(), self.round.to_json());
+        object.insert("result".to_string(), self.result.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for GameResult {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("decision".to_string(), self.decision.to_arg().to_json());
+        object.insert("winner".to_string(), self.winner.to_json());
+        object.insert("timestamp".to_string(), self.timestamp.to_json());
+        object.insert("latency".to_string(), self.latency.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for InsertionSort {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("count".to_string(), self.count.to_json());
+        object.insert("ranked".to_string(), self.ranked.to_json());
+        object.insert("next".to_string(), self.next.to_json());
+        object.insert("low".to_string(), self.low.to_json());
+        object.insert("high".to_string(), self.high.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for Tournament {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("directory".to_string(), self.directory.to_json());
+        object.insert("paths".to_string(), self.paths.to_json());
+        object.insert("players".to_string(), self.players.to_json());
+        object.insert("current_game_index".to_string(), self.current_game_index.to_json());
+        object.insert("category".to_string(), self.category.to_arg().to_json());
+        object.insert("games".to_string(), self.games.to_json());
+        object.insert("done".to_string(), self.done.to_json());
+        object.insert("round".to_string(), self.round.to_json());
+        object.insert("rounds".to_string(), self.rounds.to_json());
+        object.insert("rating_system".to_string(), self.rating_system.to_arg().to_json());
+        object.insert("seed".to_string(), self.seed.to_json());
+        object.insert("third_place".to_string(), self.third_place.to_json());
+        object.insert("sort".to_string(), self.sort.to_json());
+        Json::Object(object)
+    }
+}
+
+fn field<'a>(json: &'a Json, name: &str) -> Result<&'a Json, String> {
+    json.find(name).ok_or(format!("Session is missing '{}'", name))
+}
+
+fn number(json: &Json, name: &str) -> Result<usize, String> {
+    field(json, name)?.as_u64().map(|n| n as usize).ok_or(format!("'{}' is not a number", name))
+}
+
+fn float(json: &Json, name: &str) -> Result<f64, String> {
+    field(json, name)?.as_f64().ok_or(format!("'{}' is not a number", name))
+}
+
+fn boolean(json: &Json, name: &str) -> Result<bool, String> {
+    field(json, name)?.as_boolean().ok_or(format!("'{}' is not a boolean", name))
+}
+
+fn string(json: &Json, name: &str) -> Result<String, String> {
+    field(json, name)?.as_string().map(|s| s.to_string()).ok_or(format!("'{}' is not a string", name))
+}
+
+fn array<'a>(json: &'a Json, name: &str) -> Result<&'a Vec<Json>, String> {
+    field(json, name)?.as_array().ok_or(format!("'{}' is not a list", name))
+}
+
+fn rating_from_json(json: &Json) -> Result<Rating, String> {
+    Ok(Rating {
+        value: float(json, "value")?,
+        deviation: float(json, "deviation")?,
+        volatility: float(json, "volatility")?,
+    })
+}
+
+fn player_from_json(json: &Json) -> Result<Player, String> {
+    Ok(Player {
+        path_index: number(json, "path_index")?,
+        is_in: boolean(json, "is_in")?,
+        score: float(json, "score")? as f32,
+        byes: number(json, "byes")? as u32,
+        losses: number(json, "losses")? as u32,
+        rating: rating_from_json(field(json, "rating")?)?,
+        forfeit: boolean(json, "forfeit")?,
+    })
+}
+
+fn game_from_json(json: &Json) -> Result<Game, String> {
+    let player_guest = match field(json, "player_guest")? {
+        Json::Null => None,
+        _ => Some(number(json, "player_guest")?),
+    };
+    let result = match field(json, "result")? {
+        Json::Null => None,
+        result => Some(result_from_json(result)?),
+    };
+    Ok(Game {
+        player_home: number(json, "player_home")?,
+        player_guest,
+        round: number(json, "round")?,
+        result,
+    })
+}
+
+fn result_from_json(json: &Json) -> Result<GameResult, String> {
+    let winner = match field(json, "winner")? {
+        Json::Null => None,
+        _ => Some(number(json, "winner")?),
+    };
+    Ok(GameResult {
+        decision: Decision::from_arg(&string(json, "decision")?)?,
+        winner,
+        timestamp: number(json, "timestamp")? as u64,
+        latency: number(json, "latency")? as u64,
+    })
+}
+
+fn sort_from_json(json: &Json) -> Result<InsertionSort, String> {
+    let mut ranked = vec![];
+    for player in array(json, "ranked")? {
+        ranked.push(player.as_u64().ok_or("'ranked' contains something that is not a number")? as usize);
+    }
+    Ok(InsertionSort {
+        count: number(json, "count")?,
+        ranked,
+        next: number(json, "next")?,
+        low: number(json, "low")?,
+        high: number(json, "high")?,
+    })
+}
+
+fn tournament_from_json(json: &Json) -> Result<Tournament, String> {
+    let mut paths = vec![];
+    for path in array(json, "paths")? {
+        paths.push(path.as_string().ok_or("'paths' contains something that is not a string")?.to_string());
+    }
+    let mut players = vec![];
+    for player in array(json, "players")? {
+        players.push(player_from_json(player)?);
+    }
+    let mut games = vec![];
+    for game in array(json, "games")? {
+        games.push(game_from_json(game)?);
+    }
+
+    let mut tournament = Tournament {
+        directory: string(json, "directory")?,
+        paths,
+        players,
+        current_game_index: number(json, "current_game_index")?,
+        category: TournamentType::from_arg(&string(json, "category")?)?,
+        games,
+        done: boolean(json, "done")?,
+        round: number(json, "round")?,
+        rounds: number(json, "rounds")?,
+        rating_system: RatingSystem::from_arg(&string(json, "rating_system")?)?,
+        seed: field(json, "seed")?.as_u64().ok_or("'seed' is not a number")?,
+        third_place: boolean(json, "third_place")?,
+        sort: sort_from_json(field(json, "sort")?)?,
+        // the undo history is not part of the session, it starts fresh after resuming
+        history: vec![],
+        redo: vec![],
+        settled: vec![],
+        presented_at: 0,
+    };
+    check(&tournament)?;
+    // the time away from the session does not count towards the latency of the next decision,
+    // the clock starts again now and once more when the game is back on screen
+    tournament.mark_presented();
+    Ok(tournament)
+}
+
+// every index points at something that exists, so a damaged or edited session fails here and
+// not somewhere in the middle of the tournament
+fn check(tournament: &Tournament) -> Result<(), String> {
+    for player in &tournament.players {
+        if player.path_index >= tournament.paths.len() {
+            return Err(format!("Session has a player for picture {}, but only {} pictures", player.path_index, tournament.paths.len()));
+        }
+    }
+
+    let players = tournament.players.len();
+    for (i, game) in tournament.games.iter().enumerate() {
+        let in_game = |player: usize| player == game.player_home || game.player_guest == Some(player);
+        if game.player_home >= players || game.player_guest.is_some_and(|guest| guest >= players) {
+            return Err(format!("Game {} of the session has a player that does not exist", i));
+        }
+        if game.result.and_then(|result| result.winner).is_some_and(|winner| !in_game(winner)) {
+            return Err(format!("Game {} of the session was won by a player that did not play it", i));
+        }
+    }
+    if !tournament.done && tournament.current_game_index >= tournament.games.len() {
+        return Err(format!("The current game {} of the session does not exist", tournament.current_game_index));
+    }
+
+    let sort = &tournament.sort;
+    let mut ranked = sort.ranked.clone();
+    ranked.sort();
+    ranked.dedup();
+    let bisecting = sort.is_done() || (sort.low < sort.high && sort.high <= sort.ranked.len());
+    if sort.count != players
+        || ranked.len() != sort.ranked.len()
+        || ranked.iter().any(|player| *player >= players)
+        || sort.ranked.len() != sort.next.min(sort.count)
+        || !bisecting {
+        return Err("The sort of the session does not fit its players".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn temporary(name: &str) -> String {
+        env::temp_dir().join(format!("image_viewer_{}_{}.json", name, process::id())).display().to_string()
+    }
+
+    #[test]
+    fn saved_session_loads_the_same() {
+        for category in ["world_cup", "league", "dating", "swiss", "double_elimination", "adaptive", "sort"] {
+            let paths = (0..5).map(|i| format!("folder/{}.jpg", i)).collect();
+            let mut tournament = Tournament::new("folder".to_string(), paths, TournamentType::from_arg(category).unwrap());
+            tournament.seed = 7;
+            tournament.generate_round();
+            for decision in [Decision::Left, Decision::Draw, Decision::Right] {
+                tournament.submit(decision);
+            }
+
+            let path = temporary(&format!("session_{}", category));
+            save(&tournament, &path).unwrap();
+            let loaded = load(&path);
+            fs::remove_file(&path).unwrap();
+            let loaded = loaded.unwrap();
+            // json keeps the floats only to about the last digit
+            for (loaded, player) in loaded.players.iter().zip(&tournament.players) {
+                assert!((loaded.rating.value - player.rating.value).abs() < 1e-9, "{}", category);
+                assert!((loaded.rating.deviation - player.rating.deviation).abs() < 1e-9, "{}", category);
+                assert_eq!((loaded.path_index, loaded.is_in, loaded.score, loaded.byes, loaded.losses), (player.path_index, player.is_in, player.score, player.byes, player.losses));
+            }
+            assert_eq!(loaded.players.len(), tournament.players.len());
+            assert_eq!(loaded.games.to_json(), tournament.games.to_json(), "{}", category);
+            assert_eq!(loaded.sort.to_json(), tournament.sort.to_json(), "{}", category);
+            assert_eq!((loaded.current_game_index, loaded.done, loaded.round, loaded.rounds, loaded.seed), (tournament.current_game_index, tournament.done, tournament.round, tournament.rounds, tournament.seed));
+            assert_eq!(loaded.next_pair(), tournament.next_pair(), "{}", category);
+            assert!(loaded.presented_at >= tournament.presented_at, "{}", category);
+        }
+    }
+
+    #[test]
+    fn damaged_session_is_refused() {
+        let paths = (0..4).map(|i| i.to_string()).collect();
+        let mut tournament = Tournament::new(String::new(), paths, TournamentType::WorldCup);
+        tournament.generate_round();
+        let json = tournament.to_json().to_string();
+        assert!(tournament_from_json(&Json::from_str(&json).unwrap()).is_ok());
+
+        let damaged = json.replace("\"player_home\":0", "\"player_home\":9");
+        assert!(tournament_from_json(&Json::from_str(&damaged).unwrap()).is_err());
+        let damaged = json.replace("\"path_index\":3", "\"path_index\":4");
+        assert!(tournament_from_json(&Json::from_str(&damaged).unwrap()).is_err());
+        let damaged = json.replace("\"category\":\"world_cup\"", "\"category\":\"chess\"");
+        assert!(tournament_from_json(&Json::from_str(&damaged).unwrap()).is_err());
+    }
+}
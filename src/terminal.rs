@@ -0,0 +1,246 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use image::FilterType;
+use log::{debug};
+use rustc_serialize::base64::{ToBase64, STANDARD};
+
+use image_viewer::picture;
+use image_viewer::tournament::{Decision, Tournament};
+use super::exif;
+use super::save_session;
+
+// longest side of the thumbnail that is sent to the terminal
+const PREVIEW_SIZE: u32 = 400;
+// how many text rows the terminal scales a thumbnail into
+const PREVIEW_ROWS: u32 = 12;
+// the kitty graphics protocol only takes this much base64 per escape sequence
+const KITTY_CHUNK: usize = 4096;
+
+const HELP: &str = "A left, D right, W draw, S skip, X reject both, Z undo, Y redo, Q quit";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Preview {
+    // kitty graphics if the terminal looks like it understands them
+    Auto,
+    Kitty,
+    Off,
+}
+
+impl Preview {
+    pub fn from_arg(arg: &str) -> Result<Preview, &'static str> {
+        match arg {
+            "auto" => Ok(Preview::Auto),
+            "kitty" => Ok(Preview::Kitty),
+            "off" => Ok(Preview::Off),
+            _ => Err("Unrecognized preview"),
+        }
+    }
+}
+
+// plays the tournament in the terminal: file names, sizes and EXIF instead of a window,
+// decided with the same keys
+pub fn play(tournament: &mut Tournament, session_path: &str, preview: Preview) {
+    let kitty = match preview {
+        Preview::Auto => supports_kitty(),
+        Preview::Kitty => true,
+        Preview::Off => false,
+    };
+
+    // the terminal is restored once this goes out of scope
+    let _cbreak = Cbreak::enable();
+    show(tournament, session_path, kitty);
+    if tournament.done {
+        return;
+    }
+
+    for byte in io::stdin().lock().bytes() {
+        let key = match byte {
+            Ok(byte) => byte.to_ascii_lowercase(),
+            Err(_) => break,
+        };
+        debug!("{:?} Pressed!", key as char);
+        match key {
+            // not escape, arrow keys and the like start with it as well
+            b'q' => break,
+            // backspace or z
+            0x7f | 0x08 | b'z' => {
+                if tournament.undo() {
+                    save_session(tournament, session_path);
+                    show(tournament, session_path, kitty);
+                    if tournament.done {
+                        break;
+                    }
+                }
+            },
+            b'y' => {
+                if tournament.redo() {
+                    save_session(tournament, session_path);
+                    show(tournament, session_path, kitty);
+                    if tournament.done {
+                        break;
+                    }
+                }
+            },
+            _ => {
+                if let Some(decision) = decision_for_key(key) {
+                    tournament.submit(decision);
+                    save_session(tournament, session_path);
+                    show(tournament, session_path, kitty);
+                    if tournament.done {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn decision_for_key(key: u8) -> Option<Decision> {
+    match key {
+        b'a' => Some(Decision::Left),
+        b'd' => Some(Decision::Right),
+        b'w' => Some(Decision::Draw),
+        b's' => Some(Decision::Skip),
+        b'x' => Some(Decision::RejectBoth),
+        _ => None,
+    }
+}
+
+// clears the screen and describes the current game. A picture that can't be opened forfeits
+// this game and all the games after it, the next game is described below it.
+fn show(tournament: &mut Tournament, session_path: &str, kitty: bool) {
+    let mut clear = true;
+    while let Some((home, guest)) = tournament.next_pair() {
+        if clear {
+            print!("\x1b[2J\x1b[H");
+        }
+        println!("Round {}, game {}", tournament.round, tournament.current_game_index + 1);
+        println!();
+        let mut broken = vec![];
+        match guest {
+            Some(guest) => {
+                broken.extend(describe("Left", home, kitty));
+                println!();
+                broken.extend(describe("Right", guest, kitty));
+            },
+            None => broken.extend(describe("Picture", home, kitty)),
+        }
+        println!();
+        println!("{}", HELP);
+        let _ = io::stdout().flush();
+        if broken.is_empty() {
+            break;
+        }
+        for path in broken {
+            tournament.forfeit(&path);
+        }
+        save_session(tournament, session_path);
+        clear = false;
+        println!();
+    }
+    tournament.mark_presented();
+}
+
+// returns the path if the preview showed that the picture can't be opened
+fn describe(label: &str, path: &str, kitty: bool) -> Option<String> {
+    let name = Path::new(path).file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+    let size = match fs::metadata(path) {
+        Ok(metadata) => human_size(metadata.len()),
+        Err(_) => "missing".to_string(),
+    };
+    println!("{}: {} ({})", label, name, size);
+    if let Some(summary) = exif::summary(path) {
+        println!("  {}", summary);
+    }
+    if kitty {
+        if let Err(error) = kitty_preview(path) {
+            println!("  Could not open it ({}), it forfeits its remaining games", error);
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+fn human_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.0} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+// kitty forwards TERM over ssh, the window id only exists locally
+fn supports_kitty() -> bool {
+    env::var("KITTY_WINDOW_ID").is_ok() || env::var("TERM").map(|term| term.contains("kitty")).unwrap_or(false)
+}
+
+// sends a thumbnail as raw rgba with the kitty graphics protocol, split into chunks
+// see https://sw.kovidgoyal.net/kitty/graphics-protocol/
+fn kitty_preview(path: &str) -> Result<(), String> {
+    let image = picture::open(path)?;
+    let thumbnail = image.resize(PREVIEW_SIZE, PREVIEW_SIZE, FilterType::Triangle).to_rgba();
+    let (width, height) = thumbnail.dimensions();
+    let data = thumbnail.into_raw().to_base64(STANDARD);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let _ = if i == 0 {
+            write!(out, "\x1b_Ga=T,f=32,s={},v={},r={},m={};", width, height, PREVIEW_ROWS, more)
+        } else {
+            write!(out, "\x1b_Gm={};", more)
+        };
+        let _ = out.write_all(chunk);
+        let _ = write!(out, "\x1b\\");
+    }
+    let _ = writeln!(out);
+    Ok(())
+}
+
+// single key presses without enter and without echo, the way stty cbreak does it
+// if stdin is no terminal nothing changes and keys are read line by line
+struct Cbreak {
+    saved: Option<String>,
+}
+
+impl Cbreak {
+    fn enable() -> Cbreak {
+        let saved = stty(&["-g"]);
+        if saved.is_some() {
+            stty(&["-icanon", "-echo", "min", "1"]);
+        }
+        Cbreak { saved }
+    }
+}
+
+impl Drop for Cbreak {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.saved {
+            stty(&[saved.as_str()]);
+        }
+    }
+}
+
+// stty works on the terminal it gets as stdin, so that has to be ours
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
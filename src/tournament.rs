@@ -1,72 +1,225 @@
-use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
 use log::{debug};
-use std::process;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
-use super::draw;
+use super::rating::{Rating, RatingSystem};
+use super::sort::InsertionSort;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TournamentType {
     WorldCup,
     League,
     Dating,
+    Swiss,
+    DoubleElimination,
+    Adaptive,
+    Sort,
+}
+
+impl TournamentType {
+    pub fn from_arg(arg: &str) -> Result<TournamentType, &'static str> {
+        match arg {
+            "dating" => Ok(TournamentType::Dating),
+            "world_cup" => Ok(TournamentType::WorldCup),
+            "league" => Ok(TournamentType::League),
+            "swiss" => Ok(TournamentType::Swiss),
+            "double_elimination" => Ok(TournamentType::DoubleElimination),
+            "adaptive" => Ok(TournamentType::Adaptive),
+            "sort" => Ok(TournamentType::Sort),
+            _ => Err("Unrecognized match type"),
+        }
+    }
+
+    pub fn to_arg(self) -> &'static str {
+        match self {
+            TournamentType::Dating => "dating",
+            TournamentType::WorldCup => "world_cup",
+            TournamentType::League => "league",
+            TournamentType::Swiss => "swiss",
+            TournamentType::DoubleElimination => "double_elimination",
+            TournamentType::Adaptive => "adaptive",
+            TournamentType::Sort => "sort",
+        }
+    }
+}
+
+// what the user made of a game
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Decision {
+    Left,
+    Right,
+    Draw,
+    // no decision for now, the game is played again later
+    Skip,
+    // neither picture is any good, both are out
+    RejectBoth,
+}
+
+impl Decision {
+    pub fn from_arg(arg: &str) -> Result<Decision, &'static str> {
+        match arg {
+            "left" => Ok(Decision::Left),
+            "right" => Ok(Decision::Right),
+            "draw" => Ok(Decision::Draw),
+            "skip" => Ok(Decision::Skip),
+            "reject" => Ok(Decision::RejectBoth),
+            _ => Err("Unrecognized decision"),
+        }
+    }
+
+    pub fn to_arg(self) -> &'static str {
+        match self {
+            Decision::Left => "left",
+            Decision::Right => "right",
+            Decision::Draw => "draw",
+            Decision::Skip => "skip",
+            Decision::RejectBoth => "reject",
+        }
+    }
 }
 
-// TODO: what about rounds?
 pub struct Tournament {
+    // the folder the pictures were read from
+    pub directory: String,
     pub paths: Vec<String>,
     pub players: Vec<Player>,
     pub current_game_index: usize,
-    // world_cup, league, dating
+    // world_cup, league, dating, swiss, double_elimination, adaptive, sort
     pub category: TournamentType,
     pub games: Vec<Game>,
     pub done: bool,
+    // number of rounds generated so far
+    pub round: usize,
+    // how many rounds a swiss tournament lasts, adaptive mode plays as many games as a swiss
+    // tournament with this many rounds would
+    pub rounds: usize,
+    // rates every game with two players, regardless of the mode
+    pub rating_system: RatingSystem,
+    // makes every random choice repeatable, the same seed and decisions give the same tournament
+    pub seed: u64,
+    // only relevant for world cup, the semi final losers play for third place before the final
+    pub third_place: bool,
+    // only relevant for sort, keeps track of the order found so far
+    pub sort: InsertionSort,
+    // every decision that can still be taken back, latest last
+    pub history: Vec<Step>,
+    // decisions that were taken back and can be made again, latest last
+    pub redo: Vec<Decision>,
+    // games settled by the decision that is currently being handled
+    pub settled: Vec<usize>,
+    // when the current game was shown, in milliseconds since the unix epoch
+    pub presented_at: u64,
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct Player {
     // path: std::fs::DirEntry,
     pub path_index: usize,
-    // only relevant for world cup and dating Players
+    // only relevant for world cup, double elimination and dating Players
     pub is_in: bool,
-    // only relevant for league
-    pub score: i32,
+    // only relevant for league and swiss, a draw is worth half a point
+    pub score: f32,
+    // only relevant for swiss and world cup, how often the player got a free win for lack of an opponent
+    pub byes: u32,
+    // only relevant for double elimination, one loss drops the player into the losers' bracket
+    pub losses: u32,
+    // relevant for every mode with two players per game
+    pub rating: Rating,
+    // a player that can't play anymore (e.g. its file is gone) loses all its games automatically
+    pub forfeit: bool,
 }
 
 #[derive(Debug)]
 pub struct Game {
     pub player_home: usize,
+    // none for a dating game and for a bye in a world cup
     pub player_guest: Option<usize>,
-    pub played: bool,
+    // the round the game belongs to
+    pub round: usize,
+    // none as long as the game was not played
+    pub result: Option<GameResult>,
+}
+
+impl Game {
+    pub fn played(&self) -> bool {
+        self.result.is_some()
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct GameResult {
+    pub decision: Decision,
+    // the player that won, none for a draw, a skip, a rejection or a dating game
+    // a coin flip in a knockout already decided who won a drawn game, a bye is won by its player
+    pub winner: Option<usize>,
+    // when the decision was made, in milliseconds since the unix epoch
+    pub timestamp: u64,
+    // how long the user looked at the game before deciding, in milliseconds
+    pub latency: u64,
+}
+
+// the state of the tournament right before a decision, everything a decision may change
+// including a new round and a new swiss/adaptive/sort game
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub decision: Decision,
+    pub players: Vec<Player>,
+    // games generated after the decision are dropped again
+    pub games_len: usize,
+    // games that were settled by the decision, forfeits included
+    pub settled: Vec<usize>,
+    pub current_game_index: usize,
+    pub done: bool,
+    pub round: usize,
+    pub sort: InsertionSort,
 }
 
 impl Tournament {
-    pub fn new(path: String, category: TournamentType) -> Tournament {
+    // directory is only remembered to spot added pictures when resuming, see scan::picture_paths
+    // for getting the paths out of it
+    pub fn new(directory: String, paths: Vec<String>, category: TournamentType) -> Tournament {
         // generate the Players
         let mut tournament = Tournament {
-            paths: vec![],
+            directory,
+            paths,
             games: vec![],
             current_game_index: 0,
             players: vec![],
             category,
             done: false,
+            round: 0,
+            rounds: 0,
+            rating_system: RatingSystem::default(),
+            seed: 0,
+            third_place: false,
+            sort: InsertionSort::new(0),
+            history: vec![],
+            redo: vec![],
+            settled: vec![],
+            presented_at: now(),
         };
 
-        // add all file paths into league
-        for image_path in fs::read_dir(&path).unwrap() {
-            tournament.paths.push(image_path.unwrap().path().display().to_string());
-        }
-
         // make every image a Player
         for (i, _file) in tournament.paths.iter().enumerate() {
             // println!("Img Path: {:?}", file);
             let player = Player {
                 path_index: i,
                 is_in: true,
-                score: 0,
+                score: 0.0,
+                byes: 0,
+                losses: 0,
+                rating: Rating::default(),
+                forfeit: false,
                 // buffer: vec![],
             };
             tournament.players.push(player);
         }
+
+        tournament.sort = InsertionSort::new(tournament.players.len());
+
+        // a swiss tournament needs about log2(n) rounds to find a clear winner
+        tournament.rounds = (tournament.players.len() as f64).log2().ceil().max(1.0) as usize;
         tournament
     }
 
@@ -80,25 +233,145 @@ impl Tournament {
         self.players[self.games[self.current_game_index].player_guest.unwrap()].path_index
     }
 
+    // whether the two players already faced each other in an earlier game
+    fn have_met(&self, a: usize, b: usize) -> bool {
+        self.games.iter().any(|game| {
+            (game.player_home == a && game.player_guest == Some(b)) ||
+            (game.player_home == b && game.player_guest == Some(a))
+        })
+    }
+
+    // whether the player takes no part in any further game: it forfeited, or it was rejected in
+    // a mode where everyone else keeps playing until the end
+    fn withdrawn(&self, player: usize) -> bool {
+        let player = &self.players[player];
+        let rejected = !player.is_in && matches!(
+            self.category,
+            TournamentType::League | TournamentType::Swiss | TournamentType::Adaptive
+        );
+        player.forfeit || rejected
+    }
+
+    // the pair whose result tells us the most: evenly matched players whose ratings are still
+    // uncertain. Pairs that already met become less interesting with every rematch. Withdrawn
+    // players are left out.
+    fn most_informative_pair(&self) -> Option<(usize, usize)> {
+        let count = self.players.len();
+        let mut meetings = vec![0; count * count];
+        let mut games_played = vec![0; count];
+        for game in &self.games {
+            if let Some(guest) = game.player_guest {
+                meetings[game.player_home * count + guest] += 1;
+                meetings[guest * count + game.player_home] += 1;
+                games_played[game.player_home] += 1;
+                games_played[guest] += 1;
+            }
+        }
+
+        // elo has no deviation, so the uncertainty just shrinks with every game played
+        let variance = |i: usize| {
+            if self.rating_system.has_deviation() {
+                self.players[i].rating.deviation.powi(2)
+            } else {
+                Rating::default().deviation.powi(2) / (1 + games_played[i]) as f64
+            }
+        };
+
+        let mut best = None;
+        let mut best_information = 0.0;
+        for home in (0..count).filter(|i| !self.withdrawn(*i)) {
+            for guest in ((home + 1)..count).filter(|i| !self.withdrawn(*i)) {
+                let p = self.rating_system.expected(&self.players[home].rating, &self.players[guest].rating);
+                let information = p * (1.0 - p) * (variance(home) + variance(guest))
+                    / (1 + meetings[home * count + guest]) as f64;
+                if information > best_information {
+                    best = Some((home, guest));
+                    best_information = information;
+                }
+            }
+        }
+        best
+    }
+
+    // adds the games of the next round. A round without a game to play, e.g. a league of a single
+    // picture, ends the tournament.
     pub fn generate_round(&mut self) {
+        let first_new_game = self.games.len();
+        self.add_round();
+        if !self.games[first_new_game..].iter().any(|game| !game.played()) {
+            debug!("Round {} has no game to play", self.round);
+            self.done = true;
+        }
+    }
+
+    fn add_round(&mut self) {
+        self.round += 1;
         // WORLD CUP ROUND
         match self.category {
             TournamentType::WorldCup => {
-                for i in (0..(self.players.len() - 1)).step_by(2) {
-                    // check if there is another game after the one we are currently looking at
-                    // if yes, create a game with this and the next in the iteration
-                    // if not, give the game a free pass to the next round because what else are you gonna do
-                    if i + 1 < self.players.len() {
+                // out players stay in the list, they just don't play anymore
+                let mut alive: Vec<usize> = (0..self.players.len())
+                    .filter(|i| self.players[*i].is_in)
+                    .collect();
+                if alive.len() < 2 {
+                    self.done = true;
+                    return;
+                }
+
+                // down to the final: the losers of the semi finals play for third place first.
+                // After semi finals with a bye there is just one loser, it is third without a game.
+                if self.third_place && alive.len() == 2 {
+                    let losers = self.losers_of_round(self.round - 1);
+                    if losers.len() == 2 {
                         let game = Game {
-                            player_home: i,
-                            player_guest: Some(i+1),
-                            played: false,
+                            player_home: losers[0],
+                            player_guest: Some(losers[1]),
+                            round: self.round,
+                            result: None,
                         };
                         self.games.push(game);
                     }
-                    // if the condition is false, the Player in question does not compete
-                    // this means its bool condition of "is in" does not get touched at all,
-                    // making it automatically eligible for the next round
+                }
+
+                // with an odd number one player goes through without playing: the best seed
+                // among those with the fewest byes, so it is never the same one twice in a row
+                let bye = if alive.len() % 2 == 1 {
+                    let position = (0..alive.len())
+                        .min_by_key(|i| self.players[alive[*i]].byes)
+                        .unwrap_or(0);
+                    Some(alive.remove(position))
+                } else {
+                    None
+                };
+
+                // neighbours meet, so the seeding decides the bracket
+                for pair in alive.chunks(2) {
+                    let game = Game {
+                        player_home: pair[0],
+                        player_guest: Some(pair[1]),
+                        round: self.round,
+                        result: None,
+                    };
+                    self.games.push(game);
+                }
+
+                // the bye is a game without a guest that is won right away, it comes last so the
+                // round always starts with a game to play
+                if let Some(player) = bye {
+                    debug!("Player {} gets a bye", self.players[player].path_index);
+                    self.players[player].byes += 1;
+                    let game = Game {
+                        player_home: player,
+                        player_guest: None,
+                        round: self.round,
+                        result: Some(GameResult {
+                            decision: Decision::Left,
+                            winner: Some(player),
+                            timestamp: now(),
+                            latency: 0,
+                        }),
+                    };
+                    self.games.push(game);
                 }
             },
             // BUNDESLIGA STYLE ROUND
@@ -109,7 +382,8 @@ impl Tournament {
                         let game = Game {
                             player_home: i,
                             player_guest: Some(j),
-                            played: false,
+                            round: self.round,
+                            result: None,
                         };
                         self.games.push(game);
                     }
@@ -121,7 +395,105 @@ impl Tournament {
                     let game = Game {
                         player_home: i,
                         player_guest: None,
-                        played: false,
+                        round: self.round,
+                        result: None,
+                    };
+                    self.games.push(game);
+                }
+            },
+            TournamentType::Swiss => {
+                // line everyone up by score, best first, withdrawn players don't play anymore
+                let mut standings: Vec<usize> = (0..self.players.len())
+                    .filter(|i| !self.withdrawn(*i))
+                    .collect();
+                standings.sort_by(|a, b| self.players[*b].score.partial_cmp(&self.players[*a].score).unwrap());
+
+                // with an odd number of players the lowest ranked one that did not have a bye
+                // yet sits this round out and gets the point for free
+                if standings.len() % 2 == 1 {
+                    let position = standings.iter()
+                        .rposition(|i| self.players[*i].byes == 0)
+                        .unwrap_or(standings.len() - 1);
+                    let player = &mut self.players[standings.remove(position)];
+                    player.score += 1.0;
+                    player.byes += 1;
+                    debug!("Player {} gets a bye", player.path_index);
+                }
+
+                // everyone meets the closest ranked player they haven't played yet,
+                // only if there is none left a rematch is allowed
+                while !standings.is_empty() {
+                    let home = standings.remove(0);
+                    let position = standings.iter()
+                        .position(|guest| !self.have_met(home, *guest))
+                        .unwrap_or(0);
+                    let guest = standings.remove(position);
+                    let game = Game {
+                        player_home: home,
+                        player_guest: Some(guest),
+                        round: self.round,
+                        result: None,
+                    };
+                    self.games.push(game);
+                }
+            },
+            TournamentType::DoubleElimination => {
+                let winners_bracket: Vec<usize> = (0..self.players.len())
+                    .filter(|i| self.players[*i].losses == 0)
+                    .collect();
+                let losers_bracket: Vec<usize> = (0..self.players.len())
+                    .filter(|i| self.players[*i].losses == 1)
+                    .collect();
+
+                // both brackets are down to their champion: grand final
+                // if the losers' bracket champion wins it, both have lost once and the
+                // next round is a rematch inside the losers' bracket
+                if winners_bracket.len() == 1 && losers_bracket.len() == 1 {
+                    let game = Game {
+                        player_home: winners_bracket[0],
+                        player_guest: Some(losers_bracket[0]),
+                        round: self.round,
+                        result: None,
+                    };
+                    self.games.push(game);
+                    return;
+                }
+
+                // otherwise both brackets play among themselves, an odd one out waits a round
+                for bracket in &[winners_bracket, losers_bracket] {
+                    for pair in bracket.chunks(2) {
+                        if pair.len() == 2 {
+                            let game = Game {
+                                player_home: pair[0],
+                                player_guest: Some(pair[1]),
+                                round: self.round,
+                                result: None,
+                            };
+                            self.games.push(game);
+                        }
+                    }
+                }
+            },
+            TournamentType::Adaptive => {
+                // a round is just the single next game, picked based on everything played so far
+                if let Some((home, guest)) = self.most_informative_pair() {
+                    let game = Game {
+                        player_home: home,
+                        player_guest: Some(guest),
+                        round: self.round,
+                        result: None,
+                    };
+                    self.games.push(game);
+                }
+            },
+            TournamentType::Sort => {
+                // a round is the next comparison the sort needs, the new picture is on the left
+                if let Some((candidate, ranked)) = self.sort.next_pair() {
+                    let game = Game {
+                        player_home: candidate,
+                        player_guest: Some(ranked),
+                        round: self.round,
+                        result: None,
                     };
                     self.games.push(game);
                 }
@@ -130,79 +502,229 @@ impl Tournament {
 
     }
 
-    pub fn get_current_buffer(&self) -> draw::Buffer {
-        let game_index = self.current_game_index;
-        let game = &self.games[game_index];
+    // paths of the pictures in the current game, the guest is missing if the game has only one
+    // player. None once the tournament is finished.
+    pub fn next_pair(&self) -> Option<(&str, Option<&str>)> {
+        if self.done {
+            return None;
+        }
+        let game = self.games.get(self.current_game_index)?;
+        Some(self.pair(game))
+    }
 
-        // check whether we have one or two Players and draw accordingly
-        if game.player_guest.is_none()  {
-            let player_index = self.get_player_home_index();
-            let path= &self.paths[player_index];
-            let image = image::open(path).unwrap();
-            let buffer = draw::buffer_from_image(image);
-            buffer
-        } else {
-            let player_home_index = self.get_player_home_index();
-            let player_guest_index = self.get_player_guest_index();
-            let path_left = &self.paths[player_home_index];
-            let path_right = &self.paths[player_guest_index];
-            let image_left = image::open(path_left).unwrap();
-            let image_right = image::open(path_right).unwrap();
-            let buffer = draw::buffer_from_two_images(image_left, image_right);
-            buffer
+    // the current pair and the ones after it that are already known, so a frontend can load
+    // them ahead of time. Later rounds and sort comparisons depend on the decisions.
+    pub fn upcoming_pairs(&self, count: usize) -> Vec<(&str, Option<&str>)> {
+        if self.done {
+            return vec![];
+        }
+        self.games.iter()
+            .skip(self.current_game_index)
+            .filter(|game| !game.played())
+            .take(count)
+            .map(|game| self.pair(game))
+            .collect()
+    }
+
+    fn pair(&self, game: &Game) -> (&str, Option<&str>) {
+        let home = &self.paths[self.players[game.player_home].path_index];
+        let guest = game.player_guest.map(|guest| &self.paths[self.players[guest].path_index][..]);
+        (home, guest)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done
+    }
+
+    // the frontend calls this once the current game is actually on screen
+    pub fn mark_presented(&mut self) {
+        self.presented_at = now();
+    }
+
+    // marks the current game as played with the given decision
+    fn record(&mut self, decision: Decision) {
+        let timestamp = now();
+        self.games[self.current_game_index].result = Some(GameResult {
+            decision,
+            winner: None,
+            timestamp,
+            latency: timestamp.saturating_sub(self.presented_at),
+        });
+        self.settled.push(self.current_game_index);
+    }
+
+    // puts the current game back at the end of the queue, the original stays in the history as skipped
+    fn skip_game(&mut self) {
+        match self.category {
+            // refused before, see decide
+            TournamentType::Sort => {},
+            // adaptive mode just picks another pair, the skipped one counts as met and is less likely
+            TournamentType::Adaptive => {
+                self.record(Decision::Skip);
+                self.set_next_game();
+            },
+            _ => {
+                let game = &self.games[self.current_game_index];
+                let deferred = Game {
+                    player_home: game.player_home,
+                    player_guest: game.player_guest,
+                    round: game.round,
+                    result: None,
+                };
+                self.record(Decision::Skip);
+                self.games.push(deferred);
+                self.set_next_game();
+            }
         }
     }
 
-    pub fn settle_game(&mut self, input: String) {
-        debug!("Settle game of {:?}:", self.category);
-        self.games[self.current_game_index].played = true;
+    // settles the current game with any decision but a skip
+    pub fn settle_game(&mut self, decision: Decision) {
+        debug!("Settle game of {:?}: {:?}", self.category, decision);
+        self.record(decision);
+
+        // every game with two players is rated, no matter the mode
+        // rejecting both says nothing about which one is better
+        let game = &self.games[self.current_game_index];
+        let home = game.player_home;
+        let guest = game.player_guest;
+        if let Some(guest) = guest {
+            let score_home = match decision {
+                Decision::Left => Some(1.0),
+                Decision::Draw => Some(0.5),
+                Decision::Right => Some(0.0),
+                Decision::Skip | Decision::RejectBoth => None,
+            };
+            if let Some(score_home) = score_home {
+                let (rating_home, rating_guest) = self.rating_system.rate(
+                    self.players[home].rating,
+                    self.players[guest].rating,
+                    score_home
+                );
+                self.players[home].rating = rating_home;
+                self.players[guest].rating = rating_guest;
+            }
+        }
+
+        // rejecting both works the same in every mode: neither of them makes it
+        if decision == Decision::RejectBoth {
+            for player in Some(home).into_iter().chain(guest) {
+                self.players[player].is_in = false;
+                // out of both brackets
+                self.players[player].losses = 2;
+            }
+            // the sort still needs to put the candidate somewhere
+            if self.category == TournamentType::Sort {
+                self.sort.submit_draw();
+            }
+            return;
+        }
+
+        // knockouts need someone to go on, so a coin flip settles a draw there. The coin depends on
+        // the seed and the game only, so redoing the decision or resuming the session flips it the same way.
+        // a draw in dating means the picture is not rejected
+        let decision = match self.category {
+            TournamentType::WorldCup | TournamentType::DoubleElimination if decision == Decision::Draw => {
+                let mut coin = StdRng::seed_from_u64(self.seed.wrapping_add(self.current_game_index as u64));
+                if coin.gen() { Decision::Left } else { Decision::Right }
+            },
+            TournamentType::Dating if decision == Decision::Draw => Decision::Right,
+            _ => decision,
+        };
+        if let (Some(guest), Some(result)) = (guest, &mut self.games[self.current_game_index].result) {
+            result.winner = match decision {
+                Decision::Left => Some(home),
+                Decision::Right => Some(guest),
+                _ => None,
+            };
+        }
+
         // left swipe has the opposite meaning in dating mode
         match &self.category {
             TournamentType::Dating => {
-                if input == "left" {
+                if decision == Decision::Left {
                     let player_index = self.get_player_home_index();
                     let player = &mut self.players[player_index];
                     player.is_in = false;
                 }
             },
             TournamentType::WorldCup => {
-                if input == "left" {
-                    // guest player has lost
-                    let player_index = self.get_player_guest_index();
-                    let player = &mut self.players[player_index];
-                    player.is_in = false;
-                    debug!("brrrrrr: {:}", player.is_in);
-                } else {
-                    // home player has lost
-                    let player_index = self.get_player_home_index();
-                    let player = &mut self.players[player_index];
-                    player.is_in = false;
+                // the loser is out. In the third place game both are out already, only the
+                // result counts there.
+                let loser = if decision == Decision::Left { guest } else { Some(home) };
+                if let Some(loser) = loser {
+                    self.players[loser].is_in = false;
                 }
             },
             TournamentType::League => {
-                if input == "left" {
+                if decision == Decision::Draw {
+                    // half a point each
+                    let home_index = self.get_player_home_index();
+                    let guest_index = self.get_player_guest_index();
+                    self.players[home_index].score += 0.5;
+                    self.players[guest_index].score += 0.5;
+                } else if decision == Decision::Left {
                     // guest player has lost
                     let player_index = self.get_player_home_index();
                     let player = &mut self.players[player_index];
-                    player.score += 1;
+                    player.score += 1.0;
                     debug!("Player {}: {}", player.path_index, player.score);
                 } else {
                     // home player has lost
                     let player_index = self.get_player_guest_index();
                     let player = &mut self.players[player_index];
-                    player.score += 1;
+                    player.score += 1.0;
                     debug!("Player {}: {}", player.path_index, player.score);
                 }
+            },
+            TournamentType::Swiss => {
+                let game = &self.games[self.current_game_index];
+                let home = game.player_home;
+                let guest = game.player_guest.unwrap();
+                match decision {
+                    Decision::Left => self.players[home].score += 1.0,
+                    Decision::Draw => {
+                        self.players[home].score += 0.5;
+                        self.players[guest].score += 0.5;
+                    },
+                    _ => self.players[guest].score += 1.0,
+                }
+                debug!("Player {}: {}, Player {}: {}", home, self.players[home].score, guest, self.players[guest].score);
+            },
+            TournamentType::DoubleElimination => {
+                let game = &self.games[self.current_game_index];
+                let loser = if decision == Decision::Left {
+                    game.player_guest.unwrap()
+                } else {
+                    game.player_home
+                };
+                let player = &mut self.players[loser];
+                player.losses += 1;
+                // the second loss knocks a player out for good
+                if player.losses >= 2 {
+                    player.is_in = false;
+                }
+                debug!("Player {}: {} losses", player.path_index, player.losses);
+            },
+            TournamentType::Adaptive => {
+                // nothing to do, the rating is all that counts
+            },
+            TournamentType::Sort => {
+                if decision == Decision::Draw {
+                    self.sort.submit_draw();
+                } else {
+                    self.sort.submit(decision == Decision::Left);
+                }
             }
         }
     }
 
     pub fn set_next_game(&mut self) {
         debug!("Set next game");
-        let old_game = self.current_game_index.clone();
+        let old_game = self.current_game_index;
         for (i, game) in self.games.iter().enumerate() {
-            if !game.played {
-                self.current_game_index = i as usize;
+            if !game.played() {
+                self.current_game_index = i;
                 break;
             }
         }
@@ -212,31 +734,38 @@ impl Tournament {
             // when we are here. If we are doing world cup, we may need another round.
 
             match self.category {
-                TournamentType::WorldCup => {
-                    // update the player list to only contain players that are not out
-                    let old_players = self.players.clone();
-                    self.players = vec![];
-                    for player in old_players {
-                        if player.is_in {
-                            self.players.push(player);
-                            debug!("Player {} in new round!", player.path_index);
-                        }
-                    }
-                    // if we have three players, we just switch to league mode
-                    // why? because we are giving one player a free ticket into the next round if
-                    // the total number is odd. This would be very unfair for a 3-player-knockout.
-                    // Instead, we want everyone against everyone - which is just league mode.
-                    if self.players.len() <= 3 {
-                        self.category = TournamentType::League;
-                    }
+                // a knockout goes on until only the champion is left
+                TournamentType::WorldCup if self.players.iter().filter(|player| player.is_in).count() > 1 => {
+                    let first_new_game = self.games.len();
                     self.generate_round();
-                    self.current_game_index = 0;
-                    debug!("New World Cup Round generated - {} players!", self.players.len());
-
-                    if self.players.len() <= 1 {
-                        self.done = true;
-                    }
-                }
+                    self.current_game_index = first_new_game;
+                    debug!("World cup round {} generated", self.round);
+                },
+                TournamentType::Swiss if self.round < self.rounds => {
+                    let first_new_game = self.games.len();
+                    self.generate_round();
+                    self.current_game_index = first_new_game;
+                    debug!("Swiss round {} of {} generated", self.round, self.rounds);
+                },
+                // double elimination goes on until only one player is left with less than two losses
+                TournamentType::DoubleElimination if self.players.iter().filter(|player| player.is_in).count() > 1 => {
+                    let first_new_game = self.games.len();
+                    self.generate_round();
+                    self.current_game_index = first_new_game;
+                    debug!("Double elimination round {} generated", self.round);
+                },
+                TournamentType::Adaptive if self.round < self.rounds * self.players.len() / 2 => {
+                    let first_new_game = self.games.len();
+                    self.generate_round();
+                    self.current_game_index = first_new_game;
+                    debug!("Adaptive game {} generated", self.round);
+                },
+                TournamentType::Sort if !self.sort.is_done() => {
+                    let first_new_game = self.games.len();
+                    self.generate_round();
+                    self.current_game_index = first_new_game;
+                    debug!("Sorting player {} of {}", self.sort.next + 1, self.sort.count);
+                },
                 _ => {
                     self.done = true;
                 }
@@ -244,21 +773,449 @@ impl Tournament {
         }
     }
 
-    pub fn render_scoreboard(&self) {
-        for player in &self.players {
-            println!("Player {:?} -- Points: {:?}, In: {}", player.path_index, player.score, player.is_in);
+    // players that lost a decided game of the given round, in the order of the games
+    fn losers_of_round(&self, round: usize) -> Vec<usize> {
+        self.games.iter()
+            .filter(|game| game.round == round)
+            .filter_map(|game| {
+                let winner = game.result?.winner?;
+                let guest = game.player_guest?;
+                Some(if winner == game.player_home { guest } else { game.player_home })
+            })
+            .collect()
+    }
+
+    // knockout players by how far they got: the champion, the loser of the final, then both semi
+    // final losers with the winner of the third place game first, and so on. Players that went
+    // out in the same round are ordered by rating. In double elimination a player is only out
+    // after its second loss.
+    fn knockout_order(&self) -> Vec<usize> {
+        let lives = if self.category == TournamentType::DoubleElimination { 2 } else { 1 };
+        // the round of the game that cost each player its last life, the champion never went out
+        let mut out_in = vec![usize::MAX; self.players.len()];
+        let mut losses = vec![0; self.players.len()];
+        let mut won_last = vec![false; self.players.len()];
+        for game in &self.games {
+            let result = match game.result {
+                Some(result) if result.decision != Decision::Skip => result,
+                _ => continue,
+            };
+            for player in Some(game.player_home).into_iter().chain(game.player_guest) {
+                let won = result.winner == Some(player);
+                if !won {
+                    // a rejected player is out right away
+                    losses[player] += if result.decision == Decision::RejectBoth { lives } else { 1 };
+                    if losses[player] >= lives && out_in[player] == usize::MAX {
+                        out_in[player] = game.round;
+                    }
+                }
+                won_last[player] = won;
+            }
         }
+
+        let mut order: Vec<usize> = (0..self.players.len()).collect();
+        order.sort_by(|a, b| {
+            out_in[*b].cmp(&out_in[*a])
+                .then(won_last[*b].cmp(&won_last[*a]))
+                .then(self.players[*b].rating.value.partial_cmp(&self.players[*a].rating.value).unwrap())
+        });
+        order
     }
 
-    pub fn handle_key_press(&mut self, input: String) {
-        self.settle_game(input);
-        debug!("Game settled");
-        self.set_next_game();
-        // check whether its over
-        if self.done {
-            self.render_scoreboard();
-            process::exit(0x0100);
+    // whether the player was thrown out together with its opponent
+    fn rejected(&self, player: usize) -> bool {
+        self.games.iter().any(|game| {
+            game.result.is_some_and(|result| result.decision == Decision::RejectBoth)
+                && (game.player_home == player || game.player_guest == Some(player))
+        })
+    }
+
+    // all players, best rated first. League and swiss go by points and only then by rating, in
+    // sort mode the sorted order is used instead and players that were not sorted yet come last,
+    // world cup and double elimination go by how far everyone got.
+    pub fn standings(&self) -> Vec<Player> {
+        match self.category {
+            TournamentType::WorldCup | TournamentType::DoubleElimination => {
+                self.knockout_order().iter()
+                    .map(|i| self.players[*i])
+                    .collect()
+            },
+            TournamentType::Sort => {
+                let unsorted = (0..self.players.len()).filter(|i| !self.sort.ranked.contains(i));
+                self.sort.ranked.iter()
+                    .cloned()
+                    .chain(unsorted)
+                    .map(|i| self.players[i])
+                    .collect()
+            },
+            TournamentType::League | TournamentType::Swiss => {
+                let mut standings = self.players.clone();
+                standings.sort_by(|a, b| {
+                    b.score.partial_cmp(&a.score).unwrap()
+                        .then(b.rating.value.partial_cmp(&a.rating.value).unwrap())
+                });
+                standings
+            },
+            _ => {
+                let mut standings = self.players.clone();
+                standings.sort_by(|a, b| b.rating.value.partial_cmp(&a.rating.value).unwrap());
+                standings
+            }
+        }
+    }
+
+    // the standings as text, one player per line
+    pub fn scoreboard(&self) -> String {
+        let mut scoreboard = String::new();
+        for (rank, player) in self.standings().iter().enumerate() {
+            let rating = if self.rating_system.has_deviation() {
+                format!("{:.0} ± {:.0}", player.rating.value, player.rating.deviation)
+            } else {
+                format!("{:.0}", player.rating.value)
+            };
+            scoreboard.push_str(&format!("#{} Player {:?} -- Rating: {}, Points: {:?}, Losses: {}, In: {}\n", rank + 1, player.path_index, rating, player.score, player.losses, player.is_in));
+        }
+        scoreboard
+    }
+
+    // paths of the pictures that made it: everyone still in for dating, the `top` best placed
+    // for world cup and double elimination, the `top` best scorers for league and swiss, the `top`
+    // best rated for adaptive and the `top` first of the sorted order. Rejected pictures never
+    // make it, and neither do forfeited ones, they are broken or gone.
+    pub fn winners(&self, top: usize) -> Vec<String> {
+        match self.category {
+            TournamentType::League | TournamentType::Swiss | TournamentType::Adaptive | TournamentType::Sort => {
+                self.standings().iter()
+                    .filter(|player| player.is_in && !player.forfeit)
+                    .take(top)
+                    .map(|player| self.paths[player.path_index].clone())
+                    .collect()
+            },
+            // the champion, then the runner-up and the third place if more are wanted
+            TournamentType::WorldCup | TournamentType::DoubleElimination => {
+                self.knockout_order().into_iter()
+                    .filter(|i| !self.rejected(*i) && !self.players[*i].forfeit)
+                    .take(top)
+                    .map(|i| self.paths[self.players[i].path_index].clone())
+                    .collect()
+            },
+            TournamentType::Dating => {
+                self.players.iter()
+                    .filter(|player| player.is_in && !player.forfeit)
+                    .map(|player| self.paths[player.path_index].clone())
+                    .collect()
+            }
+        }
+    }
+
+    // settles every upcoming game a withdrawn player is part of in favour of its opponent
+    pub fn play_forfeits(&mut self) {
+        while !self.done && self.current_game_index < self.games.len() {
+            let game = &self.games[self.current_game_index];
+            let home_forfeit = self.withdrawn(game.player_home);
+            let guest_forfeit = game.player_guest.is_some_and(|guest| self.withdrawn(guest));
+            let decision = match self.category {
+                // a dating player that forfeits is simply not liked
+                TournamentType::Dating if home_forfeit => Decision::Left,
+                _ if home_forfeit => Decision::Right,
+                _ if guest_forfeit => Decision::Left,
+                _ => return,
+            };
+            debug!("Forfeit of game {}", self.current_game_index);
+            // nobody looked at this game
+            self.presented_at = now();
+            self.settle_game(decision);
+            self.set_next_game();
+        }
+    }
+
+    // takes a picture out that can't be shown, the games it is part of from the next one on
+    // go to its opponents. They become part of the latest decision, so undoing it takes the
+    // forfeit back as well and the picture forfeits again once it comes up.
+    pub fn forfeit(&mut self, path: &str) {
+        for player in self.players.iter_mut() {
+            if self.paths[player.path_index] == path {
+                player.forfeit = true;
+            }
+        }
+        self.settled.clear();
+        self.play_forfeits();
+        if let Some(step) = self.history.last_mut() {
+            step.settled.append(&mut self.settled);
+        }
+        // what could have been redone was meant for other games
+        self.redo.clear();
+    }
+
+    // settles the current game and everything that follows from it, remembering how to undo it
+    fn decide(&mut self, decision: Decision) {
+        // the sort can't go on without this very comparison, and what didn't happen can't be undone
+        if decision == Decision::Skip && self.category == TournamentType::Sort {
+            debug!("Can't skip a game in sort mode");
+            return;
         }
+
+        let mut step = Step {
+            decision,
+            players: self.players.clone(),
+            games_len: self.games.len(),
+            settled: vec![],
+            current_game_index: self.current_game_index,
+            done: self.done,
+            round: self.round,
+            sort: self.sort.clone(),
+        };
+
+        self.settled.clear();
+        if decision == Decision::Skip {
+            self.skip_game();
+        } else {
+            self.settle_game(decision);
+            debug!("Game settled");
+            self.set_next_game();
+        }
+        self.play_forfeits();
+
+        step.settled = self.settled.drain(..).collect();
+        self.history.push(step);
+        self.presented_at = now();
+    }
+
+    // takes back the latest decision, returns false if there is nothing to take back
+    pub fn undo(&mut self) -> bool {
+        let step = match self.history.pop() {
+            Some(step) => step,
+            None => return false,
+        };
+
+        self.games.truncate(step.games_len);
+        for game_index in &step.settled {
+            if let Some(game) = self.games.get_mut(*game_index) {
+                game.result = None;
+            }
+        }
+        self.players = step.players;
+        self.current_game_index = step.current_game_index;
+        self.done = step.done;
+        self.round = step.round;
+        self.sort = step.sort;
+        self.presented_at = now();
+        debug!("Undid {:?}", step.decision);
+
+        self.redo.push(step.decision);
+        true
+    }
+
+    // makes the latest decision that was taken back again, returns false if there is none
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(decision) => {
+                debug!("Redo {:?}", decision);
+                self.decide(decision);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // decides the current game and moves on to the next one
+    pub fn submit(&mut self, decision: Decision) {
+        // nothing to decide once the tournament is over
+        if self.next_pair().is_none() {
+            return;
+        }
+        // a new decision replaces whatever could have been redone
+        self.redo.clear();
+        self.decide(decision);
     }
 }
 
+// milliseconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::{self, Command};
+
+    const MODES: [TournamentType; 7] = [
+        TournamentType::WorldCup,
+        TournamentType::League,
+        TournamentType::Dating,
+        TournamentType::Swiss,
+        TournamentType::DoubleElimination,
+        TournamentType::Adaptive,
+        TournamentType::Sort,
+    ];
+
+    fn tournament(category: TournamentType, count: usize) -> Tournament {
+        let paths = (0..count).map(|i| i.to_string()).collect();
+        let mut tournament = Tournament::new(String::new(), paths, category);
+        tournament.generate_round();
+        tournament
+    }
+
+    // repeats the commands until the tournament is over, fails if that takes more than limit
+    fn play(tournament: &mut Tournament, commands: &[Command], limit: usize) {
+        let mut used = 0;
+        while !tournament.is_finished() {
+            assert!(tournament.next_pair().is_some(), "{:?} is neither finished nor has a game", tournament.category);
+            assert!(used < limit, "{:?} of {} players is not finished after {} commands", tournament.category, tournament.players.len(), used);
+            used += script::run(tournament, commands);
+        }
+        assert!(tournament.next_pair().is_none());
+    }
+
+    #[test]
+    fn every_mode_finishes_with_the_same_decision() {
+        for category in MODES {
+            for count in [0, 1, 2, 3, 7, 8] {
+                let mut tournament = tournament(category, count);
+                play(&mut tournament, &[Command::Decide(Decision::Left)], 1000);
+                assert_eq!(tournament.standings().len(), count);
+            }
+        }
+    }
+
+    #[test]
+    fn every_mode_finishes_with_mixed_decisions() {
+        let commands = script::parse("left\nright\nskip\ndraw\nundo\nredo\nright\nundo\nleft\nreject\nleft\n").unwrap();
+        for category in MODES {
+            for count in [0, 1, 2, 3, 5, 8, 9] {
+                let mut tournament = tournament(category, count);
+                play(&mut tournament, &commands, 5000);
+                assert_eq!(tournament.standings().len(), count);
+            }
+        }
+    }
+
+    #[test]
+    fn every_mode_finishes_when_a_picture_forfeits() {
+        for category in MODES {
+            let mut tournament = tournament(category, 6);
+            tournament.forfeit("2");
+            play(&mut tournament, &[Command::Decide(Decision::Right)], 1000);
+            assert!(!tournament.winners(6).contains(&"2".to_string()), "{:?}", category);
+        }
+    }
+
+    #[test]
+    fn rejected_pictures_play_no_further_game() {
+        for category in [TournamentType::League, TournamentType::Swiss, TournamentType::Adaptive] {
+            let mut tournament = tournament(category, 6);
+            let (home, guest) = tournament.next_pair().unwrap();
+            let rejected = [home.to_string(), guest.unwrap().to_string()];
+            tournament.submit(Decision::RejectBoth);
+            while let Some((home, guest)) = tournament.next_pair() {
+                assert!(!rejected.iter().any(|path| path == home || Some(path.as_str()) == guest), "{:?}", category);
+                tournament.submit(Decision::Left);
+            }
+            assert!(tournament.winners(6).iter().all(|path| !rejected.contains(path)), "{:?}", category);
+        }
+    }
+
+    #[test]
+    fn undo_takes_back_the_forfeits_of_the_decision() {
+        let mut tournament = tournament(TournamentType::League, 4);
+        tournament.submit(Decision::Left);
+        let home = tournament.next_pair().unwrap().0.to_string();
+        tournament.forfeit(&home);
+        assert!(tournament.games.iter().filter(|game| game.played()).count() > 1);
+        assert!(tournament.undo());
+        assert!(tournament.players.iter().all(|player| !player.forfeit));
+        assert!(tournament.games.iter().all(|game| !game.played()));
+        play(&mut tournament, &[Command::Decide(Decision::Left)], 100);
+        // one point per game
+        let points: f32 = tournament.players.iter().map(|player| player.score).sum();
+        assert_eq!(points, tournament.games.len() as f32);
+    }
+
+    #[test]
+    fn submit_after_the_end_does_nothing() {
+        let mut tournament = tournament(TournamentType::League, 1);
+        assert!(tournament.is_finished());
+        tournament.submit(Decision::Left);
+        assert!(tournament.games.iter().all(|game| !game.played()));
+    }
+
+    #[test]
+    fn world_cup_has_a_single_champion() {
+        for count in [2, 3, 5, 8] {
+            let mut tournament = tournament(TournamentType::WorldCup, count);
+            play(&mut tournament, &[Command::Decide(Decision::Left)], 100);
+            assert_eq!(tournament.players.iter().filter(|player| player.is_in).count(), 1);
+            assert_eq!(tournament.winners(count).len(), count);
+        }
+    }
+
+    #[test]
+    fn world_cup_has_a_podium_for_every_size() {
+        for count in [3, 4, 5, 6, 8] {
+            let mut tournament = tournament(TournamentType::WorldCup, count);
+            tournament.third_place = true;
+            play(&mut tournament, &[Command::Decide(Decision::Left)], 100);
+            let winners = tournament.winners(3);
+            assert_eq!(winners.len(), 3, "{}", count);
+            // the third is out in the semi finals at the latest
+            let third = (0..count).find(|i| tournament.paths[tournament.players[*i].path_index] == winners[2]).unwrap();
+            let final_round = tournament.games.last().unwrap().round;
+            assert!(tournament.games.iter().any(|game| game.round >= final_round - 1
+                && game.result.is_some_and(|result| result.winner.is_some_and(|winner| winner != third))
+                && (game.player_home == third || game.player_guest == Some(third))), "{}", count);
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_give_the_same_state() {
+        let mut tournament = tournament(TournamentType::Swiss, 8);
+        tournament.submit(Decision::Left);
+        tournament.submit(Decision::Right);
+        let games = tournament.games.len();
+        let current = tournament.current_game_index;
+        assert!(tournament.undo());
+        assert!(tournament.undo());
+        assert!(!tournament.undo());
+        assert!(tournament.games.iter().all(|game| !game.played()));
+        assert!(tournament.redo());
+        assert!(tournament.redo());
+        assert!(!tournament.redo());
+        assert_eq!(tournament.games.len(), games);
+        assert_eq!(tournament.current_game_index, current);
+    }
+
+    #[test]
+    fn league_winners_are_the_top_of_the_standings() {
+        let mut tournament = tournament(TournamentType::League, 5);
+        play(&mut tournament, &script::parse("left\nright\ndraw\n").unwrap(), 100);
+        let standings: Vec<String> = tournament.standings().iter()
+            .map(|player| tournament.paths[player.path_index].clone())
+            .collect();
+        assert_eq!(tournament.winners(5), standings);
+        let points: Vec<f32> = tournament.standings().iter().map(|player| player.score).collect();
+        assert!(points.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn refused_skip_leaves_nothing_to_undo() {
+        let mut tournament = tournament(TournamentType::Sort, 4);
+        tournament.submit(Decision::Skip);
+        assert!(tournament.history.is_empty());
+        assert!(!tournament.undo());
+    }
+
+    #[test]
+    fn sort_ranks_by_the_decisions() {
+        // the picture with the lower name always wins
+        let mut tournament = tournament(TournamentType::Sort, 6);
+        while let Some((left, right)) = tournament.next_pair() {
+            let decision = if left < right.unwrap() { Decision::Left } else { Decision::Right };
+            tournament.submit(decision);
+        }
+        assert_eq!(tournament.winners(3), vec!["0", "1", "2"]);
+    }
+}